@@ -0,0 +1,192 @@
+//! Synchronous wrappers around the async [`crate::polyrest::PolyRest`] and [`crate::push::PushMessenger`] clients.
+//! 
+//! Each wrapper drives its client on a private single-threaded runtime, so these must not be called from within an async context.
+//! ```
+//! use libpoly::blocking::PolyRest;
+//! 
+//! # fn run() -> Result<(), libpoly::errors::PolyRestError> {
+//! let handler = PolyRest::new("Polycom", "789", "https://192.168.1.9", true)?;
+//! let info = handler.device_info()?;
+//! println!("device info: {:?}", info);
+//! # Ok(())
+//! # }
+//! ```
+
+use std::{collections::BTreeMap, time::Duration};
+use indexmap::IndexMap;
+use tokio::runtime::{Builder, Runtime};
+use crate::{errors::PolyRestError, polyrest::{self, callctrl::{CallHandle, CallStatus, ConsultativeTransfer, DialType},
+    calllog::{CallLogEntry, CallLogKind}, mgmt::{ConfigResponseValue, ConfigSetStatus, DeviceInfo, DeviceStats, DeviceStatus, LineInfo,
+    MediaSession, NetworkInfo, NetworkStats, TransferType}, Acknowledgement}, push::{self, MessageLevel, PushCommand, PushResult, PushType}, transport::Transport};
+
+fn runtime() -> Result<Runtime, PolyRestError> {
+    Builder::new_current_thread().enable_all().build().map_err(PolyRestError::RuntimeError)
+}
+
+/// Wrap each async method of the inner client in a method that blocks on it
+macro_rules! blocking_methods {
+    ($($(#[$meta:meta])* fn $name:ident(&self $(, $arg:ident: $ty:ty)*) -> $ret:ty;)*) => {
+        $(
+            $(#[$meta])*
+            pub fn $name(&self $(, $arg: $ty)*) -> Result<$ret, PolyRestError> {
+                self.rt.block_on(self.inner.$name($($arg),*))
+            }
+        )*
+    };
+}
+
+/// A blocking version of [`crate::polyrest::PolyRest`]
+pub struct PolyRest {
+    inner: polyrest::PolyRest,
+    rt: Runtime
+}
+
+impl PolyRest {
+    /// Create a new PolyRest handler
+    pub fn new<S: Into<String>>(username: S, password: S, url: S, insecure: bool) -> Result<Self, PolyRestError> {
+        Ok(Self { inner: polyrest::PolyRest::new(username, password, url, insecure)?, rt: runtime()? })
+    }
+
+    /// Create a new PolyRest handler that sends requests over the given [`Transport`]
+    pub fn with_transport<S: Into<String>, T: Transport + 'static>(username: S, password: S, url: S, transport: T) -> Result<Self, PolyRestError> {
+        Ok(Self { inner: polyrest::PolyRest::with_transport(username, password, url, transport), rt: runtime()? })
+    }
+
+    /// see [`crate::polyrest::PolyRest::with_allow_unknown_params`]
+    pub fn with_allow_unknown_params(mut self, allow: bool) -> Self {
+        self.inner = self.inner.with_allow_unknown_params(allow);
+        self
+    }
+
+    /// see [`crate::polyrest::PolyRest::config_set`]
+    pub fn config_set<I, K, V>(&self, changes: I) -> Result<IndexMap<String, ConfigSetStatus>, PolyRestError>
+    where I: IntoIterator<Item = (K, V)>, K: Into<String>, V: Into<String> {
+        self.rt.block_on(self.inner.config_set(changes))
+    }
+
+    /// see [`crate::polyrest::PolyRest::config_get`]
+    pub fn config_get<I, S>(&self, keys: I) -> Result<IndexMap<String, ConfigResponseValue>, PolyRestError>
+    where I: IntoIterator<Item = S>, S: Into<String> {
+        self.rt.block_on(self.inner.config_get(keys))
+    }
+
+    blocking_methods! {
+        /// fetch device info
+        fn device_info(&self) -> DeviceInfo;
+        /// fetch network info
+        fn network_info(&self) -> NetworkInfo;
+        /// fetch network stats
+        fn network_stats(&self) -> NetworkStats;
+        /// fetch CPU and memory stats
+        fn device_stats(&self) -> DeviceStats;
+        /// fetch RTP stats for the media streams of every active call
+        fn media_session_stats(&self) -> Vec<MediaSession>;
+        /// fetch every parameter in the device's running config, along with the source of its value
+        fn running_config(&self) -> BTreeMap<String, ConfigResponseValue>;
+        /// fetch registration info for every line on the device
+        fn line_info(&self) -> Vec<LineInfo>;
+        /// tell the device to re-fetch its configuration from the provisioning server, without rebooting
+        fn update_configuration(&self) -> Acknowledgement;
+        /// check whether the device is idle, busy, or restarting
+        fn poll_for_status(&self) -> DeviceStatus;
+        /// block until the device reports that it is idle, or the timeout elapses
+        fn wait_until_ready(&self, timeout: Duration) -> ();
+        /// type text into the currently focused field on the phone's screen
+        fn simulate_text_input(&self, text: String) -> Acknowledgement;
+        /// restart the phone application once the device is idle
+        fn safe_restart(&self) -> Acknowledgement;
+        /// reboot the device once it is idle
+        fn safe_reboot(&self) -> Acknowledgement;
+        /// reset the device to factory defaults, wiping all local configuration
+        fn factory_reset(&self) -> Acknowledgement;
+        /// fetch the default transfer type
+        fn transfer_type(&self) -> TransferType;
+        /// set the default transfer type
+        fn set_transfer_type(&self, transfer_type: TransferType) -> Acknowledgement;
+        /// fetch a single call log from the device
+        fn call_logs(&self, kind: CallLogKind) -> Vec<CallLogEntry>;
+        /// fetch the missed, received and placed call logs from the device
+        fn all_call_logs(&self) -> Vec<CallLogEntry>;
+        /// dial a number on the given line
+        fn dial(&self, dest: String, line: u32, dial_type: DialType) -> Acknowledgement;
+        /// answer an incoming call
+        fn answer_call(&self, call_ref: CallHandle) -> Acknowledgement;
+        /// ignore an incoming call, silencing the ringer
+        fn ignore_call(&self, call_ref: CallHandle) -> Acknowledgement;
+        /// reject an incoming call
+        fn reject_call(&self, call_ref: CallHandle) -> Acknowledgement;
+        /// end an active call
+        fn end_call(&self, call_ref: CallHandle) -> Acknowledgement;
+        /// place an active call on hold
+        fn hold_call(&self, call_ref: CallHandle) -> Acknowledgement;
+        /// resume a held call
+        fn resume_call(&self, call_ref: CallHandle) -> Acknowledgement;
+        /// send DTMF digits on an active call, see [`crate::polyrest::PolyRest::send_dtmf`]
+        fn send_dtmf(&self, call: CallHandle, digits: &str) -> Vec<Acknowledgement>;
+        /// transfer a call to the given number without consulting the target first
+        fn blind_transfer(&self, call: CallHandle, dest: String) -> Acknowledgement;
+        /// start a consultative transfer: hold the call, then dial the target on the given line
+        fn start_consultative_transfer(&self, call: CallHandle, dest: String, line: u32, dial_type: DialType) -> ConsultativeTransfer;
+        /// complete a consultative transfer, connecting the held call to the consult call
+        fn complete_transfer(&self, transfer: ConsultativeTransfer) -> Acknowledgement;
+        /// cancel a consultative transfer, ending the consult call and resuming the original call
+        fn cancel_transfer(&self, transfer: ConsultativeTransfer) -> Acknowledgement;
+        /// fetch the state of every active call
+        fn call_status(&self) -> CallStatus;
+    }
+}
+
+/// A blocking version of [`crate::push::PushMessenger`]
+pub struct PushMessenger {
+    inner: push::PushMessenger,
+    rt: Runtime
+}
+
+impl PushMessenger {
+    /// Create a new push messenger
+    /// Note that the push API credentials are often different from the REST API credentials.
+    pub fn new<S: Into<String>>(username: S, password: S, url: S, insecure: bool) -> Result<Self, PolyRestError> {
+        Ok(Self { inner: push::PushMessenger::new(username, password, url, insecure)?, rt: runtime()? })
+    }
+
+    /// Create a new push messenger that sends requests over the given [`Transport`]
+    pub fn with_transport<S: Into<String>, T: Transport + 'static>(username: S, password: S, url: S, transport: T) -> Result<Self, PolyRestError> {
+        Ok(Self { inner: push::PushMessenger::with_transport(username, password, url, transport), rt: runtime()? })
+    }
+
+    /// Send a one-time message
+    /// The message_body will be inserted into the Data object of the XML push body.
+    pub fn send<S: Into<String>>(&self, level: MessageLevel, message_body: S, cmd_type: PushType) -> Result<PushResult, PolyRestError> {
+        self.rt.block_on(self.inner.send(level, message_body, cmd_type))
+    }
+
+    /// Send one or more internal URIs, such as key presses, in a single push
+    pub fn send_commands(&self, level: MessageLevel, commands: &[PushCommand]) -> Result<PushResult, PolyRestError> {
+        self.rt.block_on(self.inner.send_commands(level, commands))
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use reqwest::{Method, StatusCode};
+    use crate::transport::MockTransport;
+    use super::*;
+
+    #[test]
+    fn handles_are_send_sync() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<PolyRest>();
+        assert_send_sync::<PushMessenger>();
+    }
+
+    #[test]
+    fn blocking_request() {
+        let mock = Arc::new(MockTransport::new());
+        mock.respond(Method::POST, "/api/v1/mgmt/safeRestart", StatusCode::OK, r#"{"Status": "2000"}"#);
+        let handler = PolyRest::with_transport("Polycom", "789", "https://phone", mock.clone()).unwrap();
+        assert_eq!(handler.safe_restart().unwrap().status, "2000");
+        assert_eq!(mock.requests().len(), 1);
+    }
+}
@@ -0,0 +1,241 @@
+//! A catalog of known Polycom configuration parameters, used to validate values before they're sent to a device.
+//!
+//! Parameter names follow the Polycom admin guide: `x` and `y` stand in for a line, server or key index,
+//! so `reg.x.address` covers `reg.1.address`, `reg.2.address`, and so on.
+//! ```
+//! use libpoly::catalog;
+//!
+//! let spec = catalog::lookup("reg.1.server.1.transport").unwrap();
+//! assert!(spec.validate("UDPOnly").is_ok());
+//! assert!(spec.validate("UDP").is_err());
+//! ```
+
+use std::fmt::Display;
+use crate::{errors::PolyRestError, suggest::closest_match};
+
+/// The type of value a parameter accepts
+#[derive(Debug, Clone, Copy)]
+pub enum ParamType {
+    /// `0` or `1`
+    Bool,
+    /// An integer in the given inclusive range
+    Int { min: i64, max: i64 },
+    /// A string, optionally limited in length
+    String { max_len: Option<usize> },
+    /// One of a fixed set of strings
+    Enum(&'static [&'static str])
+}
+
+impl Display for ParamType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParamType::Bool => write!(f, "boolean (0 or 1)"),
+            ParamType::Int { min, max } => write!(f, "integer ({} to {})", min, max),
+            ParamType::String { max_len: Some(max) } => write!(f, "string (up to {} characters)", max),
+            ParamType::String { max_len: None } => write!(f, "string"),
+            ParamType::Enum(values) => write!(f, "one of {}", values.join(", "))
+        }
+    }
+}
+
+/// Describes a single configuration parameter
+#[derive(Debug)]
+pub struct ParamSpec {
+    /// The parameter name, with `x` and `y` standing in for indexes
+    pub key: &'static str,
+    pub param_type: ParamType,
+    /// The factory default, if the parameter has one
+    pub default: Option<&'static str>,
+    /// Whether the device must reboot before a change takes effect
+    pub reboot: bool,
+    pub description: &'static str,
+}
+
+impl ParamSpec {
+    /// Check a value against the parameter's type
+    pub fn validate(&self, value: &str) -> Result<(), String> {
+        match self.param_type {
+            ParamType::Bool if value == "0" || value == "1" => Ok(()),
+            ParamType::Bool => Err(format!("expected 0 or 1, got {:?}", value)),
+            ParamType::Int { min, max } => match value.parse::<i64>() {
+                Ok(num) if (min..=max).contains(&num) => Ok(()),
+                Ok(num) => Err(format!("{} is outside the range {} to {}", num, min, max)),
+                Err(_) => Err(format!("expected an integer, got {:?}", value))
+            },
+            ParamType::String { max_len: Some(max) } if value.chars().count() > max => Err(format!("longer than {} characters", max)),
+            ParamType::String { .. } => Ok(()),
+            ParamType::Enum(values) if values.contains(&value) => Ok(()),
+            ParamType::Enum(values) => Err(format!("expected one of {}, got {:?}", values.join(", "), value))
+        }
+    }
+}
+
+const TRANSPORTS: &[&str] = &["DNSnaptr", "TCPpreferred", "UDPOnly", "TLS", "TCPOnly"];
+const PORT: ParamType = ParamType::Int { min: 0, max: 65535 };
+const STRING: ParamType = ParamType::String { max_len: None };
+
+macro_rules! param {
+    ($key:expr, $ty:expr, $default:expr, $reboot:expr, $desc:expr) => {
+        ParamSpec { key: $key, param_type: $ty, default: $default, reboot: $reboot, description: $desc }
+    };
+}
+
+/// Every parameter known to the catalog
+pub static CATALOG: &[ParamSpec] = &[
+    // registration
+    param!("reg.x.address", STRING, Some(""), false, "The user part or full SIP address of the line"),
+    param!("reg.x.label", STRING, Some(""), false, "The text shown next to the line key"),
+    param!("reg.x.displayName", STRING, Some(""), false, "The display name used in SIP signaling"),
+    param!("reg.x.auth.userId", STRING, Some(""), false, "The user ID used to authenticate the line"),
+    param!("reg.x.auth.password", STRING, Some(""), false, "The password used to authenticate the line"),
+    param!("reg.x.type", ParamType::Enum(&["private", "shared"]), Some("private"), false, "Whether the line is private, or a shared line"),
+    param!("reg.x.lineKeys", ParamType::Int { min: 1, max: 34 }, Some("1"), false, "The number of line keys used by the line"),
+    param!("reg.x.server.y.address", STRING, Some(""), false, "The address of the registrar for the line"),
+    param!("reg.x.server.y.port", PORT, Some("0"), false, "The registrar port; 0 uses the default for the transport"),
+    param!("reg.x.server.y.transport", ParamType::Enum(TRANSPORTS), Some("DNSnaptr"), false, "The transport used to reach the registrar"),
+    param!("reg.x.server.y.expires", ParamType::Int { min: 10, max: 2147483647 }, Some("3600"), false, "The requested registration period, in seconds"),
+    param!("reg.x.server.y.register", ParamType::Bool, Some("1"), false, "Whether to register with the server"),
+    param!("reg.x.outboundProxy.address", STRING, Some(""), false, "The outbound proxy for the line"),
+    param!("reg.x.outboundProxy.port", PORT, Some("0"), false, "The outbound proxy port"),
+
+    // SIP
+    param!("voIpProt.server.x.address", STRING, Some(""), false, "The address of the default registrar"),
+    param!("voIpProt.server.x.port", PORT, Some("0"), false, "The default registrar port; 0 uses the default for the transport"),
+    param!("voIpProt.server.x.transport", ParamType::Enum(TRANSPORTS), Some("DNSnaptr"), false, "The transport used to reach the default registrar"),
+    param!("voIpProt.server.x.expires", ParamType::Int { min: 10, max: 2147483647 }, Some("3600"), false, "The default requested registration period, in seconds"),
+    param!("voIpProt.server.x.register", ParamType::Bool, Some("1"), false, "Whether to register with the default server"),
+    param!("voIpProt.SIP.outboundProxy.address", STRING, Some(""), false, "The default outbound proxy"),
+    param!("voIpProt.SIP.outboundProxy.port", PORT, Some("0"), false, "The default outbound proxy port"),
+    param!("voIpProt.SIP.local.port", ParamType::Int { min: 1, max: 65535 }, Some("5060"), true, "The local port used for SIP signaling"),
+
+    // device
+    param!("device.set", ParamType::Bool, Some("0"), true, "Whether device.* parameters from config files are applied"),
+    param!("device.dhcp.enabled", ParamType::Bool, Some("1"), true, "Whether the network settings are fetched by DHCP"),
+    param!("device.net.ipAddress", STRING, None, true, "The static IPv4 address of the device"),
+    param!("device.net.subnetMask", STRING, None, true, "The static IPv4 subnet mask"),
+    param!("device.net.IPgateway", STRING, None, true, "The static IPv4 default gateway"),
+    param!("device.net.vlanId", STRING, None, true, "The VLAN ID used by the device"),
+    param!("device.prov.serverName", STRING, None, true, "The address of the provisioning server"),
+    param!("device.prov.serverType", ParamType::Enum(&["0", "1", "2", "3", "4"]), None, true, "The provisioning protocol: 0 FTP, 1 TFTP, 2 HTTP, 3 HTTPS, 4 FTPS"),
+    param!("device.prov.user", STRING, None, true, "The user name for the provisioning server"),
+    param!("device.prov.password", STRING, None, true, "The password for the provisioning server"),
+    param!("device.sntp.serverName", STRING, None, true, "The SNTP server used to set the time"),
+    param!("device.syslog.serverName", STRING, None, true, "The syslog server that logs are sent to"),
+    param!("device.auth.localAdminPassword", ParamType::String { max_len: Some(32) }, None, false, "The password for the device's admin account"),
+
+    // applications
+    param!("apps.restapi.enabled", ParamType::Bool, Some("0"), false, "Whether the REST API is enabled"),
+    param!("apps.push.messageType", ParamType::Int { min: 0, max: 5 }, Some("0"), false, "Which push messages are displayed; 0 disables push"),
+    param!("apps.push.username", STRING, Some(""), false, "The user name for the push API"),
+    param!("apps.push.password", STRING, Some(""), false, "The password for the push API"),
+    param!("apps.push.serverRootURL", STRING, Some(""), false, "The root URL that relative URLs in pushed content are resolved against"),
+    param!("apps.push.secureTunnelEnabled", ParamType::Bool, Some("1"), false, "Whether push requests must be sent over a secure tunnel"),
+    param!("apps.telNotification.URL", STRING, Some(""), false, "The URL telephony notification events are sent to"),
+    param!("apps.telNotification.incomingEvent", ParamType::Bool, Some("0"), false, "Whether to send a notification on incoming calls"),
+    param!("apps.telNotification.outgoingEvent", ParamType::Bool, Some("0"), false, "Whether to send a notification on outgoing calls"),
+    param!("apps.statePolling.URL", STRING, Some(""), false, "The URL device state is sent to when polled"),
+
+    // user preferences
+    param!("up.backlight.idleIntensity", ParamType::Int { min: 0, max: 3 }, Some("1"), false, "The backlight brightness while idle"),
+    param!("up.backlight.onIntensity", ParamType::Int { min: 0, max: 3 }, Some("3"), false, "The backlight brightness while active"),
+    param!("up.screenSaver.enabled", ParamType::Bool, Some("0"), false, "Whether the screen saver is enabled"),
+    param!("up.oneTouchVoiceMail", ParamType::Bool, Some("0"), false, "Whether the Messages key dials voicemail directly"),
+    param!("up.headsetMode", ParamType::Bool, Some("0"), false, "Whether calls stay on the headset once it's used"),
+    param!("up.handsfreeMode", ParamType::Bool, Some("1"), false, "Whether the speakerphone can be used"),
+    param!("up.idleTimeOut", ParamType::Int { min: 0, max: 65535 }, Some("40"), false, "Seconds before the phone returns to the idle screen"),
+
+    // features
+    param!("feature.enhancedFeatureKeys.enabled", ParamType::Bool, Some("0"), false, "Whether enhanced feature keys are enabled"),
+    param!("feature.callRecording.enabled", ParamType::Bool, Some("0"), false, "Whether local call recording is enabled"),
+    param!("feature.presence.enabled", ParamType::Bool, Some("0"), false, "Whether presence is enabled"),
+    param!("feature.callList.enabled", ParamType::Bool, Some("1"), false, "Whether call lists are kept"),
+    param!("feature.urlDialing.enabled", ParamType::Bool, Some("1"), false, "Whether SIP URLs can be dialed"),
+];
+
+/// Replace the index segments of a key with `x` and `y`, returning the pattern and the indexes that were replaced
+fn normalize(key: &str) -> (String, Vec<&str>) {
+    let mut indexes = Vec::new();
+    let segments: Vec<&str> = key.split('.').map(|segment| {
+        if !segment.is_empty() && segment.chars().all(|c| c.is_ascii_digit()) && indexes.len() < 2 {
+            indexes.push(segment);
+            if indexes.len() == 1 { "x" } else { "y" }
+        } else {
+            segment
+        }
+    }).collect();
+    (segments.join("."), indexes)
+}
+
+/// Fill the `x` and `y` segments of a pattern back in with indexes
+fn instantiate(pattern: &str, indexes: &[&str]) -> String {
+    pattern.split('.').map(|segment| match segment {
+        "x" => indexes.first().copied().unwrap_or("x"),
+        "y" => indexes.get(1).copied().unwrap_or("y"),
+        other => other
+    }).collect::<Vec<&str>>().join(".")
+}
+
+/// Find the catalog entry for a concrete parameter name, such as `reg.1.address`
+pub fn lookup(key: &str) -> Option<&'static ParamSpec> {
+    let (pattern, _) = normalize(key);
+    CATALOG.iter().find(|spec| spec.key == pattern || spec.key == key)
+}
+
+/// Suggest a known parameter name for a key that isn't in the catalog
+pub fn suggest(key: &str) -> Option<String> {
+    let (pattern, indexes) = normalize(key);
+    closest_match(&pattern, CATALOG.iter().map(|spec| spec.key)).map(|found| instantiate(found, &indexes))
+}
+
+/// Find every parameter whose name or description contains the search term, ignoring case
+pub fn search(term: &str) -> Vec<&'static ParamSpec> {
+    let term = term.to_lowercase();
+    CATALOG.iter()
+        .filter(|spec| spec.key.to_lowercase().contains(&term) || spec.description.to_lowercase().contains(&term))
+        .collect()
+}
+
+/// Validate a parameter and value against the catalog.
+/// Keys that aren't in the catalog are rejected unless `allow_unknown` is set.
+pub fn validate(key: &str, value: &str, allow_unknown: bool) -> Result<(), PolyRestError> {
+    match lookup(key) {
+        Some(spec) => spec.validate(value).map_err(|reason| PolyRestError::InvalidValue { key: key.to_string(), reason }),
+        None if allow_unknown => Ok(()),
+        None => Err(PolyRestError::UnknownParameter { key: key.to_string(), suggestion: suggest(key) })
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lookup_indexed() {
+        assert_eq!(lookup("reg.12.server.2.port").unwrap().key, "reg.x.server.y.port");
+        assert_eq!(lookup("up.backlight.idleIntensity").unwrap().default, Some("1"));
+        assert!(lookup("reg.1.adress").is_none());
+    }
+
+    #[test]
+    fn validation() {
+        assert!(validate("reg.1.server.1.port", "5061", false).is_ok());
+        assert!(matches!(validate("reg.1.server.1.port", "70000", false), Err(PolyRestError::InvalidValue { .. })));
+        assert!(matches!(validate("up.screenSaver.enabled", "true", false), Err(PolyRestError::InvalidValue { .. })));
+        assert!(validate("some.vendor.param", "1", true).is_ok());
+
+        let err = validate("reg.2.adress", "1002", false).unwrap_err();
+        let PolyRestError::UnknownParameter { suggestion, .. } = err else { panic!("expected UnknownParameter") };
+        assert_eq!(suggestion.as_deref(), Some("reg.2.address"));
+    }
+
+    #[test]
+    fn catalog_is_consistent() {
+        for spec in CATALOG {
+            if let Some(default) = spec.default {
+                assert!(spec.validate(default).is_ok(), "default for {} is invalid", spec.key);
+            }
+            assert_eq!(CATALOG.iter().filter(|other| other.key == spec.key).count(), 1, "{} is listed twice", spec.key);
+        }
+    }
+}
@@ -0,0 +1,92 @@
+//! Helpers for Polycom XML configuration (`.cfg`) files, as served by a provisioning server.
+
+use std::collections::BTreeMap;
+use quick_xml::{escape::escape, events::Event, Reader};
+use crate::errors::PolyRestError;
+
+/// Render a set of parameters as a Polycom XML configuration file.
+///
+/// Parameters are grouped into one element per top-level prefix (`reg`, `voIpProt`, etc.), with each parameter as an attribute:
+/// ```
+/// use std::collections::BTreeMap;
+/// use libpoly::cfg::to_cfg_xml;
+///
+/// let params = BTreeMap::from([("reg.1.label".to_string(), "Front Desk".to_string())]);
+/// let cfg = to_cfg_xml(&params);
+/// assert!(cfg.contains("<reg"));
+/// assert!(cfg.contains(r#"reg.1.label="Front Desk""#));
+/// ```
+pub fn to_cfg_xml(params: &BTreeMap<String, String>) -> String {
+    let mut groups: BTreeMap<&str, Vec<(&String, &String)>> = BTreeMap::new();
+    for (key, value) in params {
+        let prefix = key.split('.').next().unwrap_or(key);
+        groups.entry(prefix).or_default().push((key, value));
+    }
+
+    let mut out = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"yes\"?>\n<polycomConfig>\n");
+    for (prefix, params) in groups {
+        out.push_str(&format!("  <{}", prefix));
+        for (key, value) in params {
+            out.push_str(&format!("\n    {}=\"{}\"", key, escape(value.as_str())));
+        }
+        out.push_str("/>\n");
+    }
+    out.push_str("</polycomConfig>\n");
+    out
+}
+
+/// Read the parameters from a Polycom XML configuration file.
+///
+/// Every attribute on every element is treated as a parameter, apart from XML namespace and schema attributes.
+/// If a parameter is set more than once, the last value wins, as it does on the phone.
+pub fn parse_cfg_xml(xml: &str) -> Result<BTreeMap<String, String>, PolyRestError> {
+    let mut reader = Reader::from_str(xml);
+    let mut params = BTreeMap::new();
+    loop {
+        match reader.read_event()? {
+            Event::Start(elem) | Event::Empty(elem) => {
+                for attr in elem.attributes() {
+                    let attr = attr.map_err(quick_xml::Error::from)?;
+                    if attr.key.prefix().is_some() || attr.key.as_ref() == b"xmlns" {
+                        continue;
+                    }
+                    let key = String::from_utf8_lossy(attr.key.as_ref()).to_string();
+                    params.insert(key, attr.unescape_value()?.to_string());
+                }
+            },
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+    Ok(params)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cfg_round_trip() {
+        let params = BTreeMap::from([
+            ("reg.1.label".to_string(), "Front & Back <Desk>".to_string()),
+            ("reg.1.address".to_string(), "1001".to_string()),
+            ("voIpProt.server.1.address".to_string(), "pbx.example.com".to_string()),
+        ]);
+        assert_eq!(parse_cfg_xml(&to_cfg_xml(&params)).unwrap(), params);
+    }
+
+    #[test]
+    fn parse_polycom_cfg() {
+        let xml = r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<polycomConfig xmlns:xsi="http://www.w3.org/2001/XMLSchema-instance" xsi:noNamespaceSchemaLocation="polycomConfig.xsd">
+  <reg reg.1.address="1001" reg.1.label="1001"/>
+  <up up.backlight.idleIntensity="1">
+    <up.oneTouch up.oneTouchVoiceMail="1"/>
+  </up>
+</polycomConfig>"#;
+        let params = parse_cfg_xml(xml).unwrap();
+        assert_eq!(params.len(), 4);
+        assert_eq!(params["up.oneTouchVoiceMail"], "1");
+    }
+}
@@ -1,7 +1,50 @@
 //! Error types for libpoly.
 
+use std::fmt::Display;
 use thiserror::Error;
 
+/// The longest response body, in characters, kept in a [`ResponseContext`]
+const MAX_BODY_LEN: usize = 256;
+
+/// Describes an error response from the device
+#[derive(Debug, Clone)]
+pub struct ResponseContext {
+    /// The URL that was requested
+    pub url: String,
+    /// The HTTP status code of the response
+    pub http_status: u16,
+    /// The Polycom `Status` code from the response body, if there was one
+    pub polycom_status: Option<String>,
+    /// The response body, truncated to a reasonable length
+    pub body: String,
+}
+
+impl ResponseContext {
+    /// Create the context for a response. Bodies longer than `MAX_BODY_LEN` (256) characters are truncated and end with `...`.
+    pub fn new<S: Into<String>>(url: S, http_status: u16, polycom_status: Option<String>, body: &str) -> Self {
+        let body = match body.char_indices().nth(MAX_BODY_LEN) {
+            Some((idx, _)) => format!("{}...", &body[..idx]),
+            None => body.to_string()
+        };
+        Self { url: url.into(), http_status, polycom_status, body }
+    }
+}
+
+impl Display for ResponseContext {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} returned HTTP {}", self.url, self.http_status)?;
+        if let Some(code) = &self.polycom_status {
+            write!(f, ", status {}", code)?;
+        }
+        write!(f, ": {}", self.body)
+    }
+}
+
+/// Format the suggestion for an unknown name, if there is one
+fn did_you_mean(suggestion: &Option<String>) -> String {
+    suggestion.as_ref().map(|s| format!(", did you mean {}?", s)).unwrap_or_default()
+}
+
 /// The main error type for libpoly
 #[derive(Error, Debug)]
 pub enum PolyRestError {
@@ -18,5 +61,72 @@ pub enum PolyRestError {
         SendError(#[from] diqwest::error::Error),
 
         #[error("error fixing XML output")]
-        XMLEscapeError(#[from] quick_xml::escape::EscapeError)
-}
\ No newline at end of file
+        XMLEscapeError(#[from] quick_xml::escape::EscapeError),
+
+        #[error("error parsing XML config")]
+        XMLError(#[from] quick_xml::Error),
+
+        #[error("invalid DTMF sequence: {0}")]
+        DtmfError(String),
+
+        #[error("error transferring call: {0}")]
+        TransferError(String),
+
+        #[error("invalid HTTP header value")]
+        HeaderError(#[from] reqwest::header::InvalidHeaderValue),
+
+        #[error("error sending request: {0}")]
+        TransportError(Box<dyn std::error::Error + Send + Sync>),
+
+        #[error("error starting async runtime")]
+        RuntimeError(#[source] std::io::Error),
+
+        #[error("timed out waiting for device: {0}")]
+        Timeout(String),
+
+        #[error("authentication failed: {0}")]
+        AuthError(ResponseContext),
+
+        #[error("REST API is disabled on the device: {0}")]
+        ApiDisabled(ResponseContext),
+
+        #[error("push API is disabled on the device: {0}")]
+        PushDisabled(ResponseContext),
+
+        #[error("push API did not send a digest challenge: {0}")]
+        MissingChallenge(ResponseContext),
+
+        #[error("invalid digest challenge from push API: {0}")]
+        InvalidChallenge(String),
+
+        #[error("invalid parameter: {0}")]
+        InvalidParameter(ResponseContext),
+
+        #[error("device is busy: {0}")]
+        DeviceBusy(ResponseContext),
+
+        #[error("endpoint is not supported by the device: {0}")]
+        UnsupportedEndpoint(ResponseContext),
+
+        #[error("device returned an error: {0}")]
+        ApiError(ResponseContext),
+
+        #[error("unknown config parameter {key}{}", did_you_mean(suggestion))]
+        UnknownParameter { key: String, suggestion: Option<String> },
+
+        #[error("invalid value for {key}: {reason}")]
+        InvalidValue { key: String, reason: String },
+
+        #[error("unknown key {key}{}", did_you_mean(suggestion))]
+        UnknownKey { key: String, suggestion: Option<String> },
+
+        #[error("invalid push command: {0}")]
+        InvalidPushCommand(String)
+}
+
+impl PolyRestError {
+    /// true for errors that can clear up on their own, like the device not answering while it restarts, or reporting that it's busy
+    pub fn is_transient(&self) -> bool {
+        matches!(self, PolyRestError::HttpError(_) | PolyRestError::TransportError(_) | PolyRestError::DeviceBusy(_))
+    }
+}
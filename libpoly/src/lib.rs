@@ -2,15 +2,26 @@
 //! ```
 //! use libpoly::polyrest::PolyRest;
 //! 
-//! let mut handler = PolyRest::new("Polycom", "789", "https://192.168.1.9", true).unwrap();
-//! let info = handler.device_info().unwrap();
+//! # async fn run() -> Result<(), libpoly::errors::PolyRestError> {
+//! let handler = PolyRest::new("Polycom", "789", "https://192.168.1.9", true)?;
+//! let info = handler.device_info().await?;
 //! println!("device info: {:?}", info);
+//! # Ok(())
+//! # }
 //! ```
+//! 
+//! The clients are async, and can be shared between tasks. Synchronous wrappers are available in `libpoly::blocking` with the `blocking` feature.
 
 use polyrest::PolyRest;
 
 pub mod polyrest;
 pub mod push;
 pub mod errors;
+pub mod cfg;
+pub mod catalog;
+pub mod transport;
+#[cfg(feature = "blocking")]
+pub mod blocking;
+mod suggest;
 
 
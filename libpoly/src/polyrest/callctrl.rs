@@ -0,0 +1,453 @@
+//! Handlers for the Call Control subset of REST APIs.
+
+use std::{convert::Infallible, fmt::Display, str::FromStr, time::Duration};
+use clap::builder::PossibleValue;
+use serde::{Deserialize, Serialize};
+use crate::{errors::PolyRestError, PolyRest};
+use super::{from_str_num, mgmt::PolyWrapper, Acknowledgement};
+
+/// A handle referencing a single call on the device.
+/// 
+/// Handles are assigned by the phone, and can be found with [`PolyRest::call_status`].
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq, Hash)]
+#[serde(transparent)]
+pub struct CallHandle(String);
+
+impl Display for CallHandle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl FromStr for CallHandle {
+    type Err = Infallible;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(Self(s.to_string()))
+    }
+}
+
+impl From<String> for CallHandle {
+    fn from(value: String) -> Self {
+        Self(value)
+    }
+}
+
+/// The direction of a call, relative to the device
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+pub enum CallDirection {
+    Incoming,
+    Outgoing,
+    #[serde(other)]
+    Unknown
+}
+
+impl Display for CallDirection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CallDirection::Incoming => write!(f, "incoming"),
+            CallDirection::Outgoing => write!(f, "outgoing"),
+            CallDirection::Unknown => write!(f, "unknown")
+        }
+    }
+}
+
+/// The state of a call, as reported by the callStatus endpoint
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(from = "String", into = "String")]
+pub enum CallState {
+    /// An incoming call is ringing
+    Offering,
+    /// An outgoing call is ringing at the far end
+    RingBack,
+    /// The call is up
+    Connected,
+    /// The call was put on hold by this device
+    Hold,
+    /// The call was put on hold by the far end
+    Held,
+    /// Any other state reported by the device
+    Other(String)
+}
+
+impl From<String> for CallState {
+    fn from(value: String) -> Self {
+        match value.as_str() {
+            "Offering" => CallState::Offering,
+            "RingBack" => CallState::RingBack,
+            "Connected" => CallState::Connected,
+            "Hold" => CallState::Hold,
+            "Held" => CallState::Held,
+            _ => CallState::Other(value)
+        }
+    }
+}
+
+impl From<CallState> for String {
+    fn from(value: CallState) -> Self {
+        value.to_string()
+    }
+}
+
+impl Display for CallState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CallState::Offering => write!(f, "Offering"),
+            CallState::RingBack => write!(f, "RingBack"),
+            CallState::Connected => write!(f, "Connected"),
+            CallState::Hold => write!(f, "Hold"),
+            CallState::Held => write!(f, "Held"),
+            CallState::Other(state) => write!(f, "{}", state)
+        }
+    }
+}
+
+// got: {"data": {"CallHandle": "0x4b8a6e8", "Type": "Outgoing", "Protocol": "Sip", "CallState": "Connected", "LineId": "1",
+// "RemotePartyName": "1002", "RemotePartyNumber": "1002", "DurationInSeconds": "12", "Muted": "0", "UIAppearanceIndex": "1*"}, "Status": "2000"}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "PascalCase")]
+/// Carries the state of a single call on the device
+pub struct CallInfo {
+    pub call_handle: CallHandle,
+    #[serde(rename(deserialize = "LineId"))]
+    pub line: String,
+    pub remote_party_name: String,
+    pub remote_party_number: String,
+    #[serde(rename(deserialize = "Type"))]
+    pub direction: CallDirection,
+    #[serde(rename(deserialize = "CallState"))]
+    pub state: CallState,
+    #[serde(rename(deserialize = "DurationInSeconds"), deserialize_with = "from_str_num")]
+    pub duration: u64,
+    pub protocol: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(untagged)]
+// Older firmware reports a single call as an object, newer firmware returns a list.
+enum CallStatusData {
+    Many(Vec<CallInfo>),
+    One(CallInfo)
+}
+
+#[derive(Debug)]
+/// Lists every active call on the device
+pub struct CallStatus {
+    pub calls: Vec<CallInfo>
+}
+
+/// The type of call to place when dialing
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub enum DialType {
+    /// Dial a SIP URI or extension
+    #[serde(rename = "SIP")]
+    Sip,
+    /// Dial a telephone number
+    #[serde(rename = "TEL")]
+    Tel
+}
+
+impl clap::ValueEnum for DialType {
+    fn value_variants<'a>() -> &'a [Self] {
+        &[Self::Sip, Self::Tel]
+    }
+
+    fn to_possible_value(&self) -> Option<clap::builder::PossibleValue> {
+        match self {
+            DialType::Sip => Some(PossibleValue::new("sip")),
+            DialType::Tel => Some(PossibleValue::new("tel"))
+        }
+    }
+}
+
+impl Display for DialType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DialType::Sip => write!(f, "sip"),
+            DialType::Tel => write!(f, "tel")
+        }
+    }
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "PascalCase")]
+/// The request body for the callctrl/dial endpoint
+pub struct DialRequest {
+    /// The number or URI to dial
+    pub dest: String,
+    /// The line to place the call on, starting at 1
+    pub line: String,
+    #[serde(rename = "Type")]
+    pub dial_type: DialType,
+}
+
+#[derive(Serialize, Debug)]
+/// The request body for endpoints that act on an existing call
+pub struct CallRequest {
+    /// The call reference, as reported by the phone
+    #[serde(rename = "Ref")]
+    pub call_ref: CallHandle,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "PascalCase")]
+/// The request body for the callctrl/sendDTMF endpoint
+pub struct DtmfRequest {
+    /// The digits to send; `0-9`, `*` and `#`
+    pub digits: String,
+    #[serde(rename = "Ref")]
+    pub call_ref: CallHandle,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "PascalCase")]
+/// The request body for the callctrl/transferCall endpoint
+pub struct TransferRequest {
+    #[serde(rename = "Ref")]
+    pub call_ref: CallHandle,
+    /// The number or URI to transfer the call to
+    pub transfer_dest: String,
+}
+
+#[derive(Debug, Clone)]
+/// A consultative transfer in progress.
+/// 
+/// The original call is on hold while the consult call to the target is up.
+/// Pass this to [`PolyRest::complete_transfer`] or [`PolyRest::cancel_transfer`] to finish it.
+pub struct ConsultativeTransfer {
+    /// The held call that is being transferred
+    pub original: CallHandle,
+    /// The call placed to the transfer target
+    pub consult: CallHandle,
+    /// The number or URI the consult call was placed to
+    pub target: String,
+}
+
+/// How many times to check the call status for the consult call after dialing
+const CONSULT_POLL_ATTEMPTS: u32 = 10;
+/// How long to wait between checks for the consult call
+const CONSULT_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// The length of a `,` pause in a DTMF sequence, matching how the phone treats pauses in dial strings.
+pub const DTMF_PAUSE: Duration = Duration::from_secs(2);
+
+/// Split a DTMF sequence into the digit bursts that should be sent between pauses.
+/// A `None` entry marks a pause.
+fn split_dtmf(digits: &str) -> Result<Vec<Option<String>>, PolyRestError> {
+    let mut parts = Vec::new();
+    let mut burst = String::new();
+    for digit in digits.chars() {
+        match digit {
+            '0'..='9' | '*' | '#' => burst.push(digit),
+            ',' => {
+                if !burst.is_empty() {
+                    parts.push(Some(std::mem::take(&mut burst)));
+                }
+                parts.push(None);
+            },
+            other => return Err(PolyRestError::DtmfError(format!("unsupported digit '{}'", other)))
+        }
+    }
+    if !burst.is_empty() {
+        parts.push(Some(burst));
+    }
+
+    if !parts.iter().any(Option::is_some) {
+        return Err(PolyRestError::DtmfError("no digits to send".to_string()))
+    }
+    Ok(parts)
+}
+
+impl PolyRest {
+    /// dial a number on the given line
+    pub async fn dial(&self, dest: String, line: u32, dial_type: DialType) -> Result<Acknowledgement, PolyRestError> {
+        let req = DialRequest{dest, line: line.to_string(), dial_type};
+        self.call_ctrl("dial", req).await
+    }
+
+    /// answer an incoming call
+    pub async fn answer_call(&self, call_ref: CallHandle) -> Result<Acknowledgement, PolyRestError> {
+        self.call_ctrl("answerCall", CallRequest{call_ref}).await
+    }
+
+    /// ignore an incoming call, silencing the ringer
+    pub async fn ignore_call(&self, call_ref: CallHandle) -> Result<Acknowledgement, PolyRestError> {
+        self.call_ctrl("ignoreCall", CallRequest{call_ref}).await
+    }
+
+    /// reject an incoming call
+    pub async fn reject_call(&self, call_ref: CallHandle) -> Result<Acknowledgement, PolyRestError> {
+        self.call_ctrl("rejectCall", CallRequest{call_ref}).await
+    }
+
+    /// end an active call
+    pub async fn end_call(&self, call_ref: CallHandle) -> Result<Acknowledgement, PolyRestError> {
+        self.call_ctrl("endCall", CallRequest{call_ref}).await
+    }
+
+    /// place an active call on hold
+    pub async fn hold_call(&self, call_ref: CallHandle) -> Result<Acknowledgement, PolyRestError> {
+        self.call_ctrl("holdCall", CallRequest{call_ref}).await
+    }
+
+    /// resume a held call
+    pub async fn resume_call(&self, call_ref: CallHandle) -> Result<Acknowledgement, PolyRestError> {
+        self.call_ctrl("resumeCall", CallRequest{call_ref}).await
+    }
+
+    /// send DTMF digits on an active call.
+    /// 
+    /// The sequence may contain `0-9`, `*` and `#`. Each `,` inserts a pause of [`DTMF_PAUSE`],
+    /// and the digits on either side of it are sent as separate requests.
+    /// Returns one acknowledgement per burst of digits.
+    pub async fn send_dtmf(&self, call: CallHandle, digits: &str) -> Result<Vec<Acknowledgement>, PolyRestError> {
+        let mut acks = Vec::new();
+        for part in split_dtmf(digits)? {
+            match part {
+                Some(digits) => acks.push(self.call_ctrl("sendDTMF", DtmfRequest{digits, call_ref: call.clone()}).await?),
+                None => tokio::time::sleep(DTMF_PAUSE).await
+            }
+        }
+        Ok(acks)
+    }
+
+    /// transfer a call to the given number without consulting the target first
+    pub async fn blind_transfer(&self, call: CallHandle, dest: String) -> Result<Acknowledgement, PolyRestError> {
+        self.call_ctrl("transferCall", TransferRequest{call_ref: call, transfer_dest: dest}).await
+    }
+
+    /// start a consultative transfer: hold the call, then dial the target on the given line.
+    pub async fn start_consultative_transfer(&self, call: CallHandle, dest: String, line: u32, dial_type: DialType) -> Result<ConsultativeTransfer, PolyRestError> {
+        let existing: Vec<CallHandle> = self.call_status().await?.calls.into_iter().map(|c| c.call_handle).collect();
+        if !existing.contains(&call) {
+            return Err(PolyRestError::TransferError(format!("no active call with handle {}", call)))
+        }
+
+        self.hold_call(call.clone()).await?;
+        self.dial(dest.clone(), line, dial_type).await?;
+
+        // the dial endpoint doesn't return a handle, so look for the new outgoing call on the line that was dialed.
+        // Anything else that shows up, like an incoming call, isn't the consult call.
+        let line = line.to_string();
+        for _ in 0..CONSULT_POLL_ATTEMPTS {
+            let consult = self.call_status().await?.calls.into_iter()
+                .find(|c| !existing.contains(&c.call_handle) && c.direction == CallDirection::Outgoing && c.line == line);
+            if let Some(consult) = consult {
+                return Ok(ConsultativeTransfer { original: call, consult: consult.call_handle, target: dest })
+            }
+            tokio::time::sleep(CONSULT_POLL_INTERVAL).await;
+        }
+
+        Err(PolyRestError::TransferError(format!("consult call to {} never appeared; call {} is still on hold", dest, call)))
+    }
+
+    /// complete a consultative transfer, connecting the held call to the target.
+    /// transferCall only takes a call and a destination, so the held call is transferred to the target the consult call reached;
+    /// the phone clears the consult leg itself.
+    pub async fn complete_transfer(&self, transfer: ConsultativeTransfer) -> Result<Acknowledgement, PolyRestError> {
+        self.call_ctrl("transferCall", TransferRequest{call_ref: transfer.original, transfer_dest: transfer.target}).await
+    }
+
+    /// cancel a consultative transfer, ending the consult call and resuming the original call
+    pub async fn cancel_transfer(&self, transfer: ConsultativeTransfer) -> Result<Acknowledgement, PolyRestError> {
+        self.end_call(transfer.consult).await?;
+        self.resume_call(transfer.original).await
+    }
+
+    /// fetch the state of every active call
+    pub async fn call_status(&self) -> Result<CallStatus, PolyRestError> {
+        let path = format!("{}/api/v1/webCallControl/callStatus", self.url);
+        let resp = self.raw_get(path).await?;
+        // the phone omits the data field entirely when there are no calls
+        let parsed: PolyWrapper<Option<CallStatusData>> = serde_json::from_str(&resp)?;
+        let calls = match parsed.data {
+            Some(CallStatusData::Many(calls)) => calls,
+            Some(CallStatusData::One(call)) => vec![call],
+            None => Vec::new()
+        };
+        Ok(CallStatus { calls })
+    }
+
+    async fn call_ctrl<R: Serialize>(&self, endpoint: &str, data: R) -> Result<Acknowledgement, PolyRestError> {
+        let req = serde_json::to_string(&PolyWrapper{data, status: None})?;
+        let path = format!("{}/api/v1/callctrl/{}", self.url, endpoint);
+        let resp = self.raw_post(path, req).await?;
+        let parsed: Acknowledgement = serde_json::from_str(&resp)?;
+        Ok(parsed)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+    use reqwest::{Method, StatusCode};
+    use serde_json::Value;
+    use crate::transport::{HttpResponse, MockTransport};
+    use super::*;
+
+    fn call_json(handle: &str, direction: &str) -> String {
+        format!(r#"{{"CallHandle": "{}", "Type": "{}", "CallState": "Connected", "LineId": "1", "RemotePartyName": "1002",
+        "RemotePartyNumber": "1002", "DurationInSeconds": "12"}}"#, handle, direction)
+    }
+
+    #[tokio::test]
+    async fn consultative_transfer() {
+        let mock = Arc::new(MockTransport::new());
+        let calls = Arc::new(Mutex::new(vec![("0x1", "Outgoing")]));
+        let status_calls = calls.clone();
+        mock.respond_with(Method::GET, "/api/v1/webCallControl/callStatus", move |_| {
+            let calls: Vec<String> = status_calls.lock().unwrap().iter().map(|(handle, direction)| call_json(handle, direction)).collect();
+            HttpResponse::new(StatusCode::OK, format!(r#"{{"data": [{}], "Status": "2000"}}"#, calls.join(",")))
+        });
+        let dial_calls = calls.clone();
+        mock.respond_with(Method::POST, "/api/v1/callctrl/dial", move |_| {
+            // an unrelated call rings in while the consult call is being placed
+            dial_calls.lock().unwrap().extend([("0x3", "Incoming"), ("0x2", "Outgoing")]);
+            HttpResponse::new(StatusCode::OK, r#"{"Status": "2000"}"#)
+        });
+        for endpoint in ["holdCall", "transferCall"] {
+            mock.respond(Method::POST, &format!("/api/v1/callctrl/{}", endpoint), StatusCode::OK, r#"{"Status": "2000"}"#);
+        }
+        let handler = PolyRest::with_transport("Polycom", "789", "https://phone", mock.clone());
+
+        let transfer = handler.start_consultative_transfer(CallHandle::from("0x1".to_string()), "1003@pbx".to_string(), 1, DialType::Sip).await.unwrap();
+        assert_eq!(transfer.consult, CallHandle::from("0x2".to_string()));
+        handler.complete_transfer(transfer).await.unwrap();
+
+        let bodies: Vec<(String, Value)> = mock.requests().iter()
+            .filter(|req| req.method == Method::POST)
+            .map(|req| (req.path(), serde_json::from_str(&req.body_str()).unwrap()))
+            .collect();
+        let endpoints: Vec<&str> = bodies.iter().map(|(path, _)| path.as_str()).collect();
+        // the phone clears the consult leg, so nothing is ended after the transfer
+        assert_eq!(endpoints, ["/api/v1/callctrl/holdCall", "/api/v1/callctrl/dial", "/api/v1/callctrl/transferCall"]);
+        assert_eq!(bodies[1].1["data"]["Type"], "SIP");
+        assert_eq!(bodies[2].1["data"], serde_json::json!({"Ref": "0x1", "TransferDest": "1003@pbx"}));
+    }
+
+    #[test]
+    fn parse_call_status() {
+        let raw = r#"{"data": {"CallHandle": "0x4b8a6e8", "Type": "Outgoing", "Protocol": "Sip", "CallState": "Connected", "LineId": "1",
+        "RemotePartyName": "1002", "RemotePartyNumber": "1002", "DurationInSeconds": "12", "Muted": "0", "UIAppearanceIndex": "1*"}, "Status": "2000"}"#;
+        let parsed: PolyWrapper<Option<CallStatusData>> = serde_json::from_str(raw).unwrap();
+        let Some(CallStatusData::One(call)) = parsed.data else { panic!("expected a single call") };
+        assert_eq!(call.call_handle, CallHandle::from("0x4b8a6e8".to_string()));
+        assert_eq!(call.direction, CallDirection::Outgoing);
+        assert_eq!(call.duration, 12);
+        assert_eq!(call.state, CallState::Connected);
+        assert_eq!(serde_json::from_str::<CallState>(r#""Dialtone""#).unwrap(), CallState::Other("Dialtone".to_string()));
+
+        let empty: PolyWrapper<Option<CallStatusData>> = serde_json::from_str(r#"{"Status": "2000"}"#).unwrap();
+        assert!(empty.data.is_none());
+    }
+
+    #[test]
+    fn dtmf_pauses() {
+        let parts = split_dtmf("12,,*#3").unwrap();
+        assert_eq!(parts, vec![Some("12".to_string()), None, None, Some("*#3".to_string())]);
+
+        assert!(split_dtmf("12a").is_err());
+        assert!(split_dtmf(",,").is_err());
+    }
+}
@@ -0,0 +1,223 @@
+//! Handlers for the call log REST APIs.
+
+use std::fmt::Display;
+use clap::builder::PossibleValue;
+use serde::{Deserialize, Deserializer, Serialize};
+use crate::{errors::PolyRestError, PolyRest};
+use super::{callctrl::CallDirection, mgmt::PolyWrapper};
+
+/// The call log list to fetch
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CallLogKind {
+    Missed,
+    Received,
+    Placed
+}
+
+impl CallLogKind {
+    fn endpoint(&self) -> &'static str {
+        match self {
+            CallLogKind::Missed => "missed",
+            CallLogKind::Received => "received",
+            CallLogKind::Placed => "placed"
+        }
+    }
+}
+
+impl clap::ValueEnum for CallLogKind {
+    fn value_variants<'a>() -> &'a [Self] {
+        &[Self::Missed, Self::Received, Self::Placed]
+    }
+
+    fn to_possible_value(&self) -> Option<clap::builder::PossibleValue> {
+        Some(PossibleValue::new(self.endpoint()))
+    }
+}
+
+impl Display for CallLogKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.endpoint())
+    }
+}
+
+/// How a logged call ended up
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CallDisposition {
+    /// An incoming call that was never answered
+    Missed,
+    /// An incoming call that was answered
+    Answered,
+    /// A call placed from the device
+    Placed
+}
+
+impl Display for CallDisposition {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CallDisposition::Missed => write!(f, "missed"),
+            CallDisposition::Answered => write!(f, "answered"),
+            CallDisposition::Placed => write!(f, "placed")
+        }
+    }
+}
+
+// got: {"data": [{"LineNumber": "1", "StartTime": "2024-08-20T09:41:12", "Duration": "PT1M32S", "RemotePartyName": "Front Desk",
+// "RemotePartyNumber": "1002", "LocalPartyName": "1001", "LocalPartyNumber": "1001", "Count": "1"}], "Status": "2000"}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "PascalCase")]
+struct RawCallLogEntry {
+    line_number: String,
+    start_time: String,
+    #[serde(deserialize_with = "from_iso_duration")]
+    duration: u64,
+    #[serde(default)]
+    remote_party_name: String,
+    #[serde(default)]
+    remote_party_number: String,
+}
+
+#[derive(Serialize, Debug, Clone)]
+/// A single entry in one of the device's call logs
+pub struct CallLogEntry {
+    pub direction: CallDirection,
+    pub disposition: CallDisposition,
+    pub remote_party_name: String,
+    pub remote_party_number: String,
+    pub line: String,
+    pub start_time: String,
+    /// The length of the call, in seconds
+    pub duration: u64,
+}
+
+/// Parse the ISO 8601 durations the phone reports call lengths in, e.g. `PT1M32S`, into whole seconds
+fn parse_iso_duration(raw: &str) -> Option<u64> {
+    let raw = raw.trim().strip_prefix('P')?;
+    let (date, time) = match raw.split_once('T') {
+        Some((date, time)) => (date, Some(time)),
+        None => (raw, None)
+    };
+    // a T needs at least one time component after it, and a duration needs at least one component
+    if time == Some("") || (date.is_empty() && time.is_none()) {
+        return None
+    }
+    // years and months have no fixed length, so only weeks and days are accepted before the T
+    let date = sum_components(date, &[('W', 604800.0), ('D', 86400.0)])?;
+    let time = sum_components(time.unwrap_or_default(), &[('H', 3600.0), ('M', 60.0), ('S', 1.0)])?;
+    Some((date + time) as u64)
+}
+
+/// add up the `<number><unit>` components of one section of a duration, using the seconds per unit given
+fn sum_components(section: &str, units: &[(char, f64)]) -> Option<f64> {
+    let mut total = 0.0;
+    let mut num = String::new();
+    for c in section.chars() {
+        if c.is_ascii_digit() || c == '.' {
+            num.push(c);
+            continue
+        }
+        let (_, secs) = units.iter().find(|(unit, _)| *unit == c)?;
+        total += num.parse::<f64>().ok()? * secs;
+        num.clear();
+    }
+    num.is_empty().then_some(total)
+}
+
+fn from_iso_duration<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u64, D::Error> {
+    let raw = String::deserialize(deserializer)?;
+    parse_iso_duration(&raw).ok_or_else(|| serde::de::Error::custom(format!("invalid call duration {}", raw)))
+}
+
+impl CallLogEntry {
+    fn from_raw(raw: RawCallLogEntry, kind: CallLogKind) -> Self {
+        let (direction, disposition) = match kind {
+            CallLogKind::Missed => (CallDirection::Incoming, CallDisposition::Missed),
+            CallLogKind::Received => (CallDirection::Incoming, CallDisposition::Answered),
+            CallLogKind::Placed => (CallDirection::Outgoing, CallDisposition::Placed),
+        };
+        Self {
+            direction,
+            disposition,
+            remote_party_name: raw.remote_party_name,
+            remote_party_number: raw.remote_party_number,
+            line: raw.line_number,
+            start_time: raw.start_time,
+            duration: raw.duration,
+        }
+    }
+}
+
+impl PolyRest {
+    /// fetch a single call log from the device
+    pub async fn call_logs(&self, kind: CallLogKind) -> Result<Vec<CallLogEntry>, PolyRestError> {
+        let path = format!("{}/api/v1/mgmt/callLogs/{}", self.url, kind.endpoint());
+        let resp = self.raw_get(path).await?;
+        // an empty log comes back without a data field
+        let parsed: PolyWrapper<Option<Vec<RawCallLogEntry>>> = serde_json::from_str(&resp)?;
+        let entries = parsed.data.unwrap_or_default().into_iter()
+            .map(|raw| CallLogEntry::from_raw(raw, kind))
+            .collect();
+        Ok(entries)
+    }
+
+    /// fetch the missed, received and placed call logs from the device
+    pub async fn all_call_logs(&self) -> Result<Vec<CallLogEntry>, PolyRestError> {
+        let mut entries = Vec::new();
+        for kind in [CallLogKind::Missed, CallLogKind::Received, CallLogKind::Placed] {
+            entries.extend(self.call_logs(kind).await?);
+        }
+        Ok(entries)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_call_logs() {
+        let raw = r#"{"data": [{"LineNumber": "1", "StartTime": "2024-08-20T09:41:12", "Duration": "PT1M32S", "RemotePartyName": "Front Desk",
+        "RemotePartyNumber": "1002", "LocalPartyName": "1001", "LocalPartyNumber": "1001", "Count": "1"},
+        {"LineNumber": "2", "StartTime": "2024-08-20T10:02:55", "Duration": "PT1H0M4S", "LocalPartyName": "1001", "LocalPartyNumber": "1001", "Count": "2"}],
+        "Status": "2000"}"#;
+        let parsed: PolyWrapper<Option<Vec<RawCallLogEntry>>> = serde_json::from_str(raw).unwrap();
+        let entries: Vec<CallLogEntry> = parsed.data.unwrap().into_iter().map(|raw| CallLogEntry::from_raw(raw, CallLogKind::Received)).collect();
+        assert_eq!(entries[0].duration, 92);
+        assert_eq!(entries[0].remote_party_name, "Front Desk");
+        assert_eq!(entries[0].direction, CallDirection::Incoming);
+        assert_eq!(entries[0].disposition, CallDisposition::Answered);
+        assert_eq!(entries[1].duration, 3604);
+        assert_eq!(entries[1].line, "2");
+        assert_eq!(entries[1].remote_party_number, "");
+
+        let empty: PolyWrapper<Option<Vec<RawCallLogEntry>>> = serde_json::from_str(r#"{"Status": "2000"}"#).unwrap();
+        assert!(empty.data.is_none());
+        let bad = r#"{"data": [{"LineNumber": "1", "StartTime": "2024-08-20T09:41:12", "Duration": "92 seconds"}]}"#;
+        assert!(serde_json::from_str::<PolyWrapper<Option<Vec<RawCallLogEntry>>>>(bad).is_err());
+    }
+
+    #[test]
+    fn kinds_and_durations() {
+        let raw = || RawCallLogEntry { line_number: "1".to_string(), start_time: String::new(), duration: 0, remote_party_name: String::new(), remote_party_number: String::new() };
+        let missed = CallLogEntry::from_raw(raw(), CallLogKind::Missed);
+        assert_eq!((missed.direction, missed.disposition), (CallDirection::Incoming, CallDisposition::Missed));
+        let placed = CallLogEntry::from_raw(raw(), CallLogKind::Placed);
+        assert_eq!((placed.direction, placed.disposition), (CallDirection::Outgoing, CallDisposition::Placed));
+
+        assert_eq!(parse_iso_duration("PT0S"), Some(0));
+        assert_eq!(parse_iso_duration("PT12.7S"), Some(12));
+        assert_eq!(parse_iso_duration("P1DT1S"), Some(86401));
+        assert_eq!(parse_iso_duration("PT5"), None);
+        assert_eq!(parse_iso_duration("1M"), None);
+        assert_eq!(parse_iso_duration("PT1M"), Some(60));
+        // M before the T is months, which have no fixed length
+        assert_eq!(parse_iso_duration("P1M"), None);
+        assert_eq!(parse_iso_duration("P1Y"), None);
+        assert_eq!(parse_iso_duration("P1W"), Some(604800));
+        assert_eq!(parse_iso_duration("P"), None);
+        assert_eq!(parse_iso_duration("PT"), None);
+        assert_eq!(parse_iso_duration("P1D"), Some(86400));
+        assert_eq!(parse_iso_duration("PTS"), None);
+    }
+}
@@ -1,9 +1,12 @@
 //! Handlers for the Management* subset of REST APIs.
 
-use std::collections::HashMap;
-use crate::{errors::PolyRestError, PolyRest};
-use reqwest::{header::CONTENT_TYPE, Method};
+use std::{collections::{BTreeMap, HashMap}, fmt::Display, time::{Duration, Instant}};
+use crate::{catalog, errors::PolyRestError, PolyRest};
+use clap::builder::PossibleValue;
+use indexmap::IndexMap;
+use reqwest::Method;
 use serde::{Deserialize, Serialize};
+use super::{callctrl::CallHandle, from_opt_str_num, from_str_num, Acknowledgement, STATUS_SUCCESS};
 
 #[derive(Deserialize, Serialize)]
 #[serde(rename_all = "PascalCase")]
@@ -17,7 +20,7 @@ pub struct PolyWrapper<ApiResponse> {
 // got: {"data": {"ModelNumber": "VVX 411", "FirmwareRelease": "5.5.0.22173", "DeviceType": "hardwareEndpoint", "MACAddress": "64167fcacee2", 
 // "DeviceVendor": "Polycom", "UpTimeSinceLastReboot": "0 Day 6:16:10", "IPV4Address": "192.168.1.9", "IPV6Address": "::", "AttachedHardware": {"EM": []}}, "Status": "2000"}
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Serialize, Debug)]
 #[serde(rename_all = "PascalCase")]
 /// Returns device info for the given Polycom device
 pub struct DeviceInfo {
@@ -35,7 +38,7 @@ pub struct DeviceInfo {
 }
 
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Serialize, Debug)]
 #[serde(rename_all = "PascalCase")]
 /// Carries network config for the device
 pub struct NetworkInfo {
@@ -88,7 +91,7 @@ pub struct NetworkInfo {
     pub wifi: Option<NetworkInfoWifi>
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Serialize, Debug)]
 /// Carries Wi-Fi info for the device, if it exists
 pub struct NetworkInfoWifi {
     #[serde(rename(deserialize = "Signal Strength"))]
@@ -114,6 +117,205 @@ pub struct NetworkStats {
     pub tx_packets: String, 
 }
 
+// got: {"data": {"CPU": {"Current": "12.4", "Average": "9.8"}, "Memory": {"Total": "249132", "Used": "187404", "Free": "61728"}}, "Status": "2000"}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "PascalCase")]
+/// Carries CPU and memory usage for the device
+pub struct DeviceStats {
+    #[serde(rename(deserialize = "CPU"))]
+    pub cpu: CpuStats,
+    pub memory: MemoryStats,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(try_from = "BTreeMap<String, String>")]
+/// CPU load, as a percentage
+pub struct CpuStats {
+    pub current: f64,
+    /// Every load average the device reports, keyed by its field name.
+    /// Most firmware only reports `Average`; anything else starting with `Average` (e.g. `Average5Min`) is kept as well.
+    pub averages: BTreeMap<String, f64>,
+}
+
+impl TryFrom<BTreeMap<String, String>> for CpuStats {
+    type Error = String;
+    fn try_from(raw: BTreeMap<String, String>) -> Result<Self, Self::Error> {
+        let parse = |key: &str, val: &str| val.trim().parse::<f64>().map_err(|err| format!("invalid CPU {}: {}", key, err));
+        let current = raw.get("Current").ok_or("missing CPU Current")?;
+        let averages = raw.iter()
+            .filter(|(key, _)| key.starts_with("Average"))
+            .map(|(key, val)| Ok((key.clone(), parse(key, val)?)))
+            .collect::<Result<_, String>>()?;
+        Ok(Self { current: parse("Current", current)?, averages })
+    }
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "PascalCase")]
+/// Memory usage, in kilobytes
+pub struct MemoryStats {
+    #[serde(deserialize_with = "from_str_num")]
+    pub total: u64,
+    #[serde(deserialize_with = "from_str_num")]
+    pub used: u64,
+    #[serde(deserialize_with = "from_str_num")]
+    pub free: u64,
+}
+
+// got: {"data": [{"Ref": "0x4b8a6e8", "Streams": [{"Category": "Audio", "Codec": "G.722", "PacketsSent": "6120", "PacketsReceived": "6098",
+// "PacketsLost": "3", "Jitter": "4", "Latency": "21", "MOSLQ": "4.1", "MOSCQ": "4.0"}]}], "Status": "2000"}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "PascalCase")]
+/// Carries the media streams for a single call
+pub struct MediaSession {
+    #[serde(rename(deserialize = "Ref"))]
+    pub call_ref: CallHandle,
+    pub streams: Vec<MediaStreamStats>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "PascalCase")]
+/// RTP metrics for a single media stream
+pub struct MediaStreamStats {
+    /// The stream type, e.g. "Audio" or "Video"
+    pub category: String,
+    pub codec: String,
+    #[serde(deserialize_with = "from_str_num")]
+    pub packets_sent: u64,
+    #[serde(deserialize_with = "from_str_num")]
+    pub packets_received: u64,
+    #[serde(deserialize_with = "from_str_num")]
+    pub packets_lost: u64,
+    /// Interarrival jitter, in milliseconds, if reported for the stream
+    #[serde(default, deserialize_with = "from_opt_str_num")]
+    pub jitter: Option<f64>,
+    /// Round-trip latency, in milliseconds, if reported for the stream
+    #[serde(default, deserialize_with = "from_opt_str_num")]
+    pub latency: Option<f64>,
+    /// Listening-quality MOS, if reported by the firmware
+    #[serde(rename(deserialize = "MOSLQ"), default, deserialize_with = "from_opt_str_num")]
+    pub mos_lq: Option<f64>,
+    /// Conversational-quality MOS, if reported by the firmware
+    #[serde(rename(deserialize = "MOSCQ"), default, deserialize_with = "from_opt_str_num")]
+    pub mos_cq: Option<f64>,
+}
+
+/// The result of setting a single config key
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub enum ConfigSetStatus {
+    Success,
+    /// The device rejected the value, with the given Polycom status code
+    Failed(String),
+    /// The device didn't report a status for the key, or for the request as a whole, so it may not have been applied
+    Unknown
+}
+
+impl ConfigSetStatus {
+    fn from_code(code: &str) -> Self {
+        if code == STATUS_SUCCESS {
+            ConfigSetStatus::Success
+        } else {
+            ConfigSetStatus::Failed(code.to_string())
+        }
+    }
+
+    pub fn is_success(&self) -> bool {
+        *self == ConfigSetStatus::Success
+    }
+}
+
+impl Display for ConfigSetStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigSetStatus::Success => write!(f, "ok"),
+            ConfigSetStatus::Failed(code) => write!(f, "failed ({})", code),
+            ConfigSetStatus::Unknown => write!(f, "unknown (no status from device)")
+        }
+    }
+}
+
+/// Work out the per-key results of a config/set request.
+/// Some firmware reports a status per key in the data field, others only return the overall status.
+/// A response that doesn't acknowledge a key at all leaves it [`ConfigSetStatus::Unknown`], rather than assuming it was applied.
+fn parse_config_set_response(keys: Vec<String>, resp: PolyWrapper<Option<HashMap<String, serde_json::Value>>>) -> IndexMap<String, ConfigSetStatus> {
+    let overall = resp.status;
+    let per_key = resp.data.unwrap_or_default();
+    keys.into_iter().map(|key| {
+        let code = match per_key.get(&key) {
+            Some(serde_json::Value::String(code)) => Some(code.as_str()),
+            Some(serde_json::Value::Object(obj)) => obj.get("Status").and_then(|s| s.as_str()).or(overall.as_deref()),
+            _ => overall.as_deref()
+        };
+        let status = code.map_or(ConfigSetStatus::Unknown, ConfigSetStatus::from_code);
+        (key, status)
+    }).collect()
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "PascalCase")]
+/// The request body for the simulateTextInput endpoint
+pub struct TextInputRequest {
+    pub text: String,
+}
+
+/// The availability of the device, as reported by pollForStatus
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+pub enum DeviceState {
+    /// The device is ready to accept requests
+    #[serde(alias = "Available")]
+    Idle,
+    /// The device is in a call, or processing another request
+    Busy,
+    /// The device is restarting, or applying new configuration
+    #[serde(alias = "Rebooting", alias = "Updating")]
+    Restarting,
+    #[serde(other)]
+    Unknown
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "PascalCase")]
+/// Carries the response from the pollForStatus endpoint
+pub struct DeviceStatus {
+    pub state: DeviceState,
+}
+
+/// How often [`PolyRest::wait_until_ready`] polls the device
+pub const READY_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// The SIP registration state of a line
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+pub enum RegistrationStatus {
+    #[serde(alias = "registered")]
+    Registered,
+    #[serde(alias = "unregistered")]
+    Unregistered,
+    #[serde(other)]
+    Unknown
+}
+
+// got: {"data": [{"LineNumber": "1", "SIPAddress": "1001@pbx.example.com", "Label": "1001", "RegistrationStatus": "registered",
+// "ProxyAddress": "pbx.example.com", "Port": "5060", "Transport": "UDPOnly", "LineType": "private", "UserID": "1001", "Protocol": "SIP"}], "Status": "2000"}
+
+#[derive(Deserialize, Serialize, Debug)]
+#[serde(rename_all = "PascalCase")]
+/// Carries the registration info for a single line on the device
+pub struct LineInfo {
+    pub line_number: String,
+    #[serde(rename(deserialize = "SIPAddress"))]
+    pub sip_address: String,
+    pub label: String,
+    pub registration_status: RegistrationStatus,
+    pub proxy_address: String,
+    pub port: Option<String>,
+    pub transport: Option<String>,
+    pub line_type: String,
+    #[serde(rename(deserialize = "UserID"))]
+    pub user_id: Option<String>,
+}
+
 #[derive(Deserialize, Serialize, Debug)]
 #[serde(rename_all = "PascalCase")]
 /// Wraps the raw config response when making a call to the config/get REST endpoint
@@ -122,66 +324,307 @@ pub struct ConfigResponseValue {
     pub source: String,
 }
 
+/// The transfer type the device uses by default when the Transfer key is pressed
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+pub enum TransferType {
+    Blind,
+    Consultative
+}
+
+impl clap::ValueEnum for TransferType {
+    fn value_variants<'a>() -> &'a [Self] {
+        &[Self::Blind, Self::Consultative]
+    }
+
+    fn to_possible_value(&self) -> Option<clap::builder::PossibleValue> {
+        match self {
+            TransferType::Blind => Some(PossibleValue::new("blind")),
+            TransferType::Consultative => Some(PossibleValue::new("consultative"))
+        }
+    }
+}
+
+impl Display for TransferType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TransferType::Blind => write!(f, "blind"),
+            TransferType::Consultative => write!(f, "consultative")
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+#[serde(rename_all = "PascalCase")]
+/// Wraps the transfer type for the transferType get and set endpoints
+pub struct TransferTypeValue {
+    #[serde(rename = "Type")]
+    pub transfer_type: TransferType
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "PascalCase")]
+struct RunningConfigEntry {
+    name: String,
+    value: String,
+    source: String,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(untagged)]
+// depending on firmware, the running config is either keyed like config/get, or a list of named entries
+enum RunningConfigData {
+    Map(BTreeMap<String, ConfigResponseValue>),
+    List(Vec<RunningConfigEntry>)
+}
+
+/// Check a config key against a pattern, where `*` matches any run of characters.
+pub fn matches_pattern(pattern: &str, key: &str) -> bool {
+    let mut parts = pattern.split('*');
+    // split always yields at least one item
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = key.strip_prefix(first) else { return false };
+
+    let mut parts: Vec<&str> = parts.collect();
+    let Some(last) = parts.pop() else { return rest.is_empty() };
+    for part in parts {
+        match rest.find(part) {
+            Some(idx) => rest = &rest[idx + part.len()..],
+            None => return false
+        }
+    }
+    rest.ends_with(last)
+}
+
+/// Convert an uptime string as reported by the device, e.g. `0 Day 6:16:10`, into seconds.
+pub fn uptime_seconds(uptime: &str) -> Option<u64> {
+    let (days, time) = match uptime.trim().split_once(' ') {
+        Some((days, rest)) => (days.parse::<u64>().ok()?, rest.split_whitespace().last()?),
+        None => (0, uptime.trim())
+    };
+    let mut seconds = 0;
+    for part in time.split(':') {
+        seconds = seconds * 60 + part.parse::<u64>().ok()?;
+    }
+    Some(days * 86400 + seconds)
+}
+
 impl PolyRest {
     /// fetch device info
-    pub fn device_info(&mut self) -> Result<DeviceInfo, PolyRestError> {
+    pub async fn device_info(&self) -> Result<DeviceInfo, PolyRestError> {
         let path = format!("{}/api/v1/mgmt/device/info", self.url);
-        let resp = self.raw_get(path)?;
+        let resp = self.raw_get(path).await?;
         let parsed: PolyWrapper<DeviceInfo> = serde_json::from_str(&resp)?;
         Ok(parsed.data)
     }
 
     /// fetch network info
-    pub fn network_info(&mut self) -> Result<NetworkInfo, PolyRestError> {
+    pub async fn network_info(&self) -> Result<NetworkInfo, PolyRestError> {
         let path = format!("{}/api/v1/mgmt/network/info", self.url);
-        let resp = self.raw_get(path)?;
+        let resp = self.raw_get(path).await?;
         let parsed: PolyWrapper<NetworkInfo> = serde_json::from_str(&resp)?;
         Ok(parsed.data)
     }
 
     /// fetch network stats
-    pub fn network_stats(&mut self) -> Result<NetworkStats, PolyRestError> {
+    pub async fn network_stats(&self) -> Result<NetworkStats, PolyRestError> {
         let path = format!("{}/api/v1/mgmt/network/stats", self.url);
-        let resp = self.raw_get(path)?;
+        let resp = self.raw_get(path).await?;
         let parsed: PolyWrapper<NetworkStats> = serde_json::from_str(&resp)?;
         Ok(parsed.data)
     }
 
-    /// set a config value
-    pub fn config_set(&mut self, key: String, value: String) -> Result<String, PolyRestError> {
-        let setter: HashMap<String, String> = HashMap::from([(key, value)]);
-        let req = serde_json::to_string(&PolyWrapper{data: setter, status: None})?;
+    /// fetch CPU and memory stats
+    pub async fn device_stats(&self) -> Result<DeviceStats, PolyRestError> {
+        let path = format!("{}/api/v1/mgmt/device/stats", self.url);
+        let resp = self.raw_get(path).await?;
+        let parsed: PolyWrapper<DeviceStats> = serde_json::from_str(&resp)?;
+        Ok(parsed.data)
+    }
+
+    /// fetch RTP stats for the media streams of every active call
+    pub async fn media_session_stats(&self) -> Result<Vec<MediaSession>, PolyRestError> {
+        let path = format!("{}/api/v1/mgmt/media/sessionStats", self.url);
+        let resp = self.raw_get(path).await?;
+        // no data field is returned when there are no active calls
+        let parsed: PolyWrapper<Option<Vec<MediaSession>>> = serde_json::from_str(&resp)?;
+        Ok(parsed.data.unwrap_or_default())
+    }
 
-        let path = format!("{}/api/v1/mgmt/config/set", self.url);
-        let req = self.client.request(Method::POST, path)
-        .basic_auth(&self.username, Some(&self.password)).body(req)
-        .header(CONTENT_TYPE, "application/json").build()?;
+    /// fetch every parameter in the device's running config, along with the source of its value
+    pub async fn running_config(&self) -> Result<BTreeMap<String, ConfigResponseValue>, PolyRestError> {
+        let path = format!("{}/api/v1/mgmt/device/runningConfig", self.url);
+        let resp = self.raw_get(path).await?;
+        let parsed: PolyWrapper<RunningConfigData> = serde_json::from_str(&resp)?;
+        let config = match parsed.data {
+            RunningConfigData::Map(config) => config,
+            RunningConfigData::List(entries) => entries.into_iter()
+                .map(|entry| (entry.name, ConfigResponseValue{value: entry.value, source: entry.source}))
+                .collect()
+        };
+        Ok(config)
+    }
+
+    /// fetch registration info for every line on the device
+    pub async fn line_info(&self) -> Result<Vec<LineInfo>, PolyRestError> {
+        let path = format!("{}/api/v1/mgmt/lineInfo", self.url);
+        let resp = self.raw_get(path).await?;
+        let parsed: PolyWrapper<Vec<LineInfo>> = serde_json::from_str(&resp)?;
+        Ok(parsed.data)
+    }
+
+    /// tell the device to re-fetch its configuration from the provisioning server, without rebooting
+    pub async fn update_configuration(&self) -> Result<Acknowledgement, PolyRestError> {
+        self.mgmt_action("updateConfiguration").await
+    }
+
+    /// check whether the device is idle, busy, or restarting
+    pub async fn poll_for_status(&self) -> Result<DeviceStatus, PolyRestError> {
+        let path = format!("{}/api/v1/mgmt/pollForStatus", self.url);
+        let resp = self.raw_get(path).await?;
+        let parsed: PolyWrapper<DeviceStatus> = serde_json::from_str(&resp)?;
+        Ok(parsed.data)
+    }
+
+    /// wait until the device reports that it is idle, or the timeout elapses.
+    /// 
+    /// A device that can't be reached or is busy is assumed to be restarting, and polling continues; any other error is returned straight away.
+    /// Firmware without pollForStatus is ready as soon as it answers a device info request.
+    pub async fn wait_until_ready(&self, timeout: Duration) -> Result<(), PolyRestError> {
+        let start = Instant::now();
+        loop {
+            let last_state = match self.poll_for_status().await {
+                Ok(status) if status.state == DeviceState::Idle => return Ok(()),
+                Ok(status) => format!("{:?}", status.state),
+                Err(PolyRestError::UnsupportedEndpoint(_)) => match self.device_info().await {
+                    Ok(_) => return Ok(()),
+                    Err(err) if err.is_transient() => err.to_string(),
+                    Err(err) => return Err(err)
+                },
+                Err(err) if err.is_transient() => err.to_string(),
+                Err(err) => return Err(err)
+            };
+
+            if start.elapsed() >= timeout {
+                return Err(PolyRestError::Timeout(format!("device not ready after {:?}, last state: {}", timeout, last_state)))
+            }
+            tokio::time::sleep(READY_POLL_INTERVAL).await;
+        }
+    }
+
+    /// type text into the currently focused field on the phone's screen
+    pub async fn simulate_text_input(&self, text: String) -> Result<Acknowledgement, PolyRestError> {
+        let req = serde_json::to_string(&PolyWrapper{data: TextInputRequest{text}, status: None})?;
+        let path = format!("{}/api/v1/mgmt/simulateTextInput", self.url);
+        let resp = self.raw_post(path, req).await?;
+        let parsed: Acknowledgement = serde_json::from_str(&resp)?;
+        Ok(parsed)
+    }
+
+    /// restart the phone application once the device is idle
+    pub async fn safe_restart(&self) -> Result<Acknowledgement, PolyRestError> {
+        self.mgmt_action("safeRestart").await
+    }
+
+    /// reboot the device once it is idle
+    pub async fn safe_reboot(&self) -> Result<Acknowledgement, PolyRestError> {
+        self.mgmt_action("safeReboot").await
+    }
+
+    /// reset the device to factory defaults, wiping all local configuration
+    pub async fn factory_reset(&self) -> Result<Acknowledgement, PolyRestError> {
+        self.mgmt_action("factoryReset").await
+    }
+
+    async fn mgmt_action(&self, endpoint: &str) -> Result<Acknowledgement, PolyRestError> {
+        let path = format!("{}/api/v1/mgmt/{}", self.url, endpoint);
+        let resp = self.raw_post(path, String::new()).await?;
+        let parsed: Acknowledgement = serde_json::from_str(&resp)?;
+        Ok(parsed)
+    }
+
+    /// fetch the default transfer type
+    pub async fn transfer_type(&self) -> Result<TransferType, PolyRestError> {
+        let path = format!("{}/api/v1/mgmt/transferType/get", self.url);
+        let resp = self.raw_get(path).await?;
+        let parsed: PolyWrapper<TransferTypeValue> = serde_json::from_str(&resp)?;
+        Ok(parsed.data.transfer_type)
+    }
+
+    /// set the default transfer type
+    pub async fn set_transfer_type(&self, transfer_type: TransferType) -> Result<Acknowledgement, PolyRestError> {
+        let req = serde_json::to_string(&PolyWrapper{data: TransferTypeValue{transfer_type}, status: None})?;
+        let path = format!("{}/api/v1/mgmt/transferType/set", self.url);
+        let resp = self.raw_post(path, req).await?;
+        let parsed: Acknowledgement = serde_json::from_str(&resp)?;
+        Ok(parsed)
+    }
 
-        let resp = self.client.execute(req)?.error_for_status()?;
-        let raw_resp = resp.bytes()?;
-        let resp_str = String::from_utf8_lossy(&raw_resp);
-        Ok(resp_str.to_string())
+    /// set a batch of config values in a single request, returning the result for each key in the order given.
+    /// 
+    /// Every value is checked against the [`catalog`] first, and nothing is sent if any fail.
+    /// Unknown keys are rejected unless [`PolyRest::with_allow_unknown_params`] is set.
+    pub async fn config_set<I, K, V>(&self, changes: I) -> Result<IndexMap<String, ConfigSetStatus>, PolyRestError>
+    where I: IntoIterator<Item = (K, V)>, K: Into<String>, V: Into<String> {
+        let setter: IndexMap<String, String> = changes.into_iter().map(|(k, v)| (k.into(), v.into())).collect();
+        if setter.is_empty() {
+            return Ok(IndexMap::new())
+        }
+        for (key, value) in &setter {
+            catalog::validate(key, value, self.allow_unknown_params)?;
+        }
+        let req = serde_json::to_string(&PolyWrapper{data: &setter, status: None})?;
 
+        let path = format!("{}/api/v1/mgmt/config/set", self.url);
+        // a failure here may still carry per-key results, so leave the status to parse_config_set_response
+        let resp = self.raw_request(Method::POST, path, Some(req), false).await?;
+        let parsed: PolyWrapper<Option<HashMap<String, serde_json::Value>>> = serde_json::from_str(&resp)?;
+        Ok(parse_config_set_response(setter.into_keys().collect(), parsed))
     }
 
-    /// fetch a config value for the given string
-    pub fn config_get(&mut self, value: String) -> Result<HashMap<String, ConfigResponseValue>, PolyRestError> {
-        let config: Vec<String> = vec![value];
-        let req =  serde_json::to_string(&PolyWrapper{data: config, status: None})?;
+    /// fetch the config values for the given keys in a single request.
+    /// 
+    /// Keys may contain `*` wildcards, such as `reg.1.*`, which are expanded against the running config.
+    /// Values are returned in the order they were requested; keys the device doesn't know are left out.
+    pub async fn config_get<I, S>(&self, keys: I) -> Result<IndexMap<String, ConfigResponseValue>, PolyRestError>
+    where I: IntoIterator<Item = S>, S: Into<String> {
+        let config = self.expand_config_keys(keys.into_iter().map(Into::into).collect()).await?;
+        if config.is_empty() {
+            return Ok(IndexMap::new())
+        }
+        let req =  serde_json::to_string(&PolyWrapper{data: &config, status: None})?;
 
         let path = format!("{}/api/v1/mgmt/config/get", self.url);
-        let req = self.client.request(Method::POST, path)
-        .basic_auth(&self.username, Some(&self.password)).body(req)
-        .header(CONTENT_TYPE, "application/json")
-        .build()?;
+        let resp_str = self.raw_post(path, req).await?;
+        let mut parsed: PolyWrapper<HashMap<String, ConfigResponseValue>> = serde_json::from_str(&resp_str)?;
 
-        let resp = self.client.execute(req)?.error_for_status()?;
-        let raw_resp = resp.bytes()?;   
-        
-        let resp_str = String::from_utf8_lossy(&raw_resp);
-        let parsed: PolyWrapper<HashMap<String, ConfigResponseValue>> = serde_json::from_str(&resp_str)?;
+        let ordered = config.into_iter()
+            .filter_map(|key| parsed.data.remove_entry(&key))
+            .collect();
+        Ok(ordered)
+    }
 
-        Ok(parsed.data)
+    /// expand any wildcard patterns into the matching keys from the running config, dropping duplicates
+    async fn expand_config_keys(&self, keys: Vec<String>) -> Result<Vec<String>, PolyRestError> {
+        let mut running: Option<BTreeMap<String, ConfigResponseValue>> = None;
+        let mut expanded: Vec<String> = Vec::new();
+        for key in keys {
+            if !key.contains('*') {
+                if !expanded.contains(&key) {
+                    expanded.push(key);
+                }
+                continue;
+            }
+
+            if running.is_none() {
+                running = Some(self.running_config().await?);
+            }
+            for name in running.iter().flat_map(|config| config.keys()) {
+                if matches_pattern(&key, name) && !expanded.contains(name) {
+                    expanded.push(name.clone());
+                }
+            }
+        }
+        Ok(expanded)
     }
 
 }
@@ -189,14 +632,163 @@ impl PolyRest {
 
 #[cfg(test)]
 mod tests {
+    use std::sync::Arc;
+    use reqwest::{header::AUTHORIZATION, StatusCode};
+    use crate::transport::MockTransport;
     use super::*;
 
+    #[tokio::test]
+    async fn it_works() {
+        let mock = Arc::new(MockTransport::new());
+        mock.respond(Method::GET, "/api/v1/mgmt/device/info", StatusCode::OK, r#"{"data": {"ModelNumber": "VVX 411", "FirmwareRelease": "5.5.0.22173",
+        "DeviceType": "hardwareEndpoint", "MACAddress": "64167fcacee2", "DeviceVendor": "Polycom", "UpTimeSinceLastReboot": "0 Day 6:16:10",
+        "IPV4Address": "192.168.1.9", "IPV6Address": "::", "AttachedHardware": {"EM": []}}, "Status": "2000"}"#);
+        let handler = PolyRest::with_transport("Polycom", "789", "https://192.168.1.9", mock.clone());
+
+        let resp = handler.device_info().await.unwrap();
+        assert_eq!(resp.mac_address, "64167fcacee2");
+
+        let requests = mock.requests();
+        assert_eq!(requests[0].url, "https://192.168.1.9/api/v1/mgmt/device/info");
+        assert_eq!(requests[0].headers[AUTHORIZATION], "Basic UG9seWNvbTo3ODk=");
+    }
+
+    #[tokio::test]
+    async fn ready_errors() {
+        let wait = |mock: MockTransport| async move {
+            let handler = PolyRest::with_transport("Polycom", "789", "https://phone", mock);
+            // errors that won't clear up are returned straight away instead of waiting out the timeout
+            tokio::time::timeout(Duration::from_secs(5), handler.wait_until_ready(Duration::from_secs(300))).await.unwrap()
+        };
+
+        let mock = MockTransport::new();
+        mock.respond(Method::GET, "/api/v1/mgmt/pollForStatus", StatusCode::UNAUTHORIZED, "");
+        assert!(matches!(wait(mock).await, Err(PolyRestError::AuthError(_))));
+
+        // without pollForStatus, answering device info means the device is up
+        let mock = MockTransport::new();
+        mock.respond(Method::GET, "/api/v1/mgmt/device/info", StatusCode::OK, r#"{"data": {"ModelNumber": "VVX 411", "FirmwareRelease": "5.5.0.22173",
+        "DeviceType": "hardwareEndpoint", "MACAddress": "64167fcacee2", "DeviceVendor": "Polycom", "UpTimeSinceLastReboot": "0 Day 0:00:10",
+        "IPV4Address": "192.168.1.9", "IPV6Address": "::", "AttachedHardware": {"EM": []}}, "Status": "2000"}"#);
+        wait(mock).await.unwrap();
+
+        let mock = MockTransport::new();
+        assert!(matches!(wait(mock).await, Err(PolyRestError::UnsupportedEndpoint(_))));
+    }
+
+    #[tokio::test]
+    async fn config_set_request() {
+        let mock = Arc::new(MockTransport::new());
+        mock.respond(Method::POST, "/api/v1/mgmt/config/set", StatusCode::OK, r#"{"Status": "2000"}"#);
+        let handler = PolyRest::with_transport("Polycom", "789", "https://phone", mock.clone());
+
+        let results = handler.config_set([("reg.1.label", "Desk"), ("up.backlight.idleIntensity", "1")]).await.unwrap();
+        assert!(results.values().all(ConfigSetStatus::is_success));
+        let body: serde_json::Value = serde_json::from_str(&mock.requests()[0].body_str()).unwrap();
+        assert_eq!(body["data"]["reg.1.label"], "Desk");
+
+        // invalid values are caught before anything is sent
+        assert!(handler.config_set([("up.backlight.idleIntensity", "bright")]).await.is_err());
+        assert_eq!(mock.requests().len(), 1);
+    }
+
     #[test]
-    fn it_works() {
-        let mut handler = PolyRest::new("Polycom".to_string(), "789".to_string(), "https://192.168.1.9".to_string(), true).unwrap();
+    fn parse_device_stats() {
+        let raw = r#"{"data": {"CPU": {"Current": "12.4", "Average": "9.8"}, "Memory": {"Total": "249132", "Used": "187404", "Free": "61728"}}, "Status": "2000"}"#;
+        let parsed: PolyWrapper<DeviceStats> = serde_json::from_str(raw).unwrap();
+        assert_eq!(parsed.data.cpu.current, 12.4);
+        assert_eq!(parsed.data.cpu.averages["Average"], 9.8);
+        assert_eq!(parsed.data.memory.used, 187404);
 
-        let resp =handler.device_info().unwrap();
+        let raw = r#"{"Current": "3", "Average": "2.5", "Average5Min": "4", "Average15Min": "6.5", "Cores": "2"}"#;
+        let cpu: CpuStats = serde_json::from_str(raw).unwrap();
+        assert_eq!(cpu.averages.len(), 3);
+        assert_eq!(cpu.averages["Average15Min"], 6.5);
+        assert!(serde_json::from_str::<CpuStats>(r#"{"Current": "high"}"#).is_err());
+    }
+
+    #[test]
+    fn parse_media_session_stats() {
+        let raw = r#"{"data": [{"Ref": "0x4b8a6e8", "Streams": [{"Category": "Audio", "Codec": "G.722", "PacketsSent": "6120", "PacketsReceived": "6098",
+        "PacketsLost": "3", "Jitter": "4", "Latency": "21", "MOSLQ": "4.1", "MOSCQ": ""}]}], "Status": "2000"}"#;
+        let parsed: PolyWrapper<Option<Vec<MediaSession>>> = serde_json::from_str(raw).unwrap();
+        let stream = &parsed.data.unwrap()[0].streams[0];
+        assert_eq!(stream.packets_lost, 3);
+        assert_eq!(stream.mos_lq, Some(4.1));
+        assert_eq!(stream.mos_cq, None);
+        assert_eq!(stream.jitter, Some(4.0));
 
-        println!("got: {:?}", resp);
+        // one stream without jitter or latency doesn't stop the rest from parsing
+        let raw = r#"{"data": [{"Ref": "0x4b8a6e8", "Streams": [{"Category": "Video", "Codec": "H.264", "PacketsSent": "120", "PacketsReceived": "118",
+        "PacketsLost": "0"}, {"Category": "Audio", "Codec": "G.722", "PacketsSent": "6120", "PacketsReceived": "6098", "PacketsLost": "3",
+        "Jitter": "4", "Latency": "21"}]}], "Status": "2000"}"#;
+        let parsed: PolyWrapper<Option<Vec<MediaSession>>> = serde_json::from_str(raw).unwrap();
+        let streams = &parsed.data.unwrap()[0].streams;
+        assert_eq!((streams[0].jitter, streams[0].latency), (None, None));
+        assert_eq!(streams[1].latency, Some(21.0));
+    }
+
+    #[test]
+    fn parse_running_config() {
+        let raw = r#"{"data": [{"Name": "reg.1.address", "Value": "1001", "Source": "configFile"}], "Status": "2000"}"#;
+        let parsed: PolyWrapper<RunningConfigData> = serde_json::from_str(raw).unwrap();
+        assert!(matches!(parsed.data, RunningConfigData::List(ref entries) if entries[0].source == "configFile"));
+
+        let raw = r#"{"data": {"reg.1.address": {"Value": "1001", "Source": "configFile"}}, "Status": "2000"}"#;
+        let parsed: PolyWrapper<RunningConfigData> = serde_json::from_str(raw).unwrap();
+        assert!(matches!(parsed.data, RunningConfigData::Map(ref config) if config["reg.1.address"].value == "1001"));
+    }
+
+    #[test]
+    fn parse_config_set() {
+        let keys = vec!["reg.1.label".to_string(), "reg.1.bogus".to_string()];
+        let resp = serde_json::from_str(r#"{"Status": "2000"}"#).unwrap();
+        let results = parse_config_set_response(keys.clone(), resp);
+        assert!(results.values().all(ConfigSetStatus::is_success));
+
+        let resp = serde_json::from_str(r#"{"Status": "4000", "data": {"reg.1.label": "2000", "reg.1.bogus": {"Status": "4009"}}}"#).unwrap();
+        let results = parse_config_set_response(keys.clone(), resp);
+        assert_eq!(results["reg.1.label"], ConfigSetStatus::Success);
+        assert_eq!(results["reg.1.bogus"], ConfigSetStatus::Failed("4009".to_string()));
+
+        // nothing acknowledged the change, so don't report it as applied
+        let resp = serde_json::from_str(r#"{}"#).unwrap();
+        let results = parse_config_set_response(vec!["reg.1.label".to_string()], resp);
+        assert_eq!(results["reg.1.label"], ConfigSetStatus::Unknown);
+        assert!(!results["reg.1.label"].is_success());
+
+        let resp = serde_json::from_str(r#"{"data": {"reg.1.label": "2000", "reg.1.bogus": {}}}"#).unwrap();
+        let results = parse_config_set_response(keys, resp);
+        assert_eq!(results["reg.1.label"], ConfigSetStatus::Success);
+        assert_eq!(results["reg.1.bogus"], ConfigSetStatus::Unknown);
+    }
+
+    #[test]
+    fn wildcard_patterns() {
+        assert!(matches_pattern("reg.1.*", "reg.1.address"));
+        assert!(!matches_pattern("reg.1.*", "reg.10.address"));
+        assert!(matches_pattern("reg.*.address", "reg.12.address"));
+        assert!(!matches_pattern("reg.*.address", "reg.1.auth.userId"));
+        assert!(matches_pattern("reg.1.address", "reg.1.address"));
+        assert!(!matches_pattern("reg.1.address", "reg.1.addressX"));
+        assert!(matches_pattern("*", "anything"));
+    }
+
+    #[test]
+    fn parse_uptime() {
+        assert_eq!(uptime_seconds("0 Day 6:16:10"), Some(6 * 3600 + 16 * 60 + 10));
+        assert_eq!(uptime_seconds("2 days 0:00:05"), Some(2 * 86400 + 5));
+        assert_eq!(uptime_seconds("garbage"), None);
+    }
+
+    #[test]
+    fn parse_line_info() {
+        let raw = r#"{"data": [{"LineNumber": "1", "SIPAddress": "1001@pbx.example.com", "Label": "1001", "RegistrationStatus": "registered",
+        "ProxyAddress": "pbx.example.com", "Port": "5060", "Transport": "UDPOnly", "LineType": "private", "UserID": "1001", "Protocol": "SIP"},
+        {"LineNumber": "2", "SIPAddress": "", "Label": "", "RegistrationStatus": "Unknown Status", "ProxyAddress": "", "LineType": "private"}], "Status": "2000"}"#;
+        let parsed: PolyWrapper<Vec<LineInfo>> = serde_json::from_str(raw).unwrap();
+        assert_eq!(parsed.data.len(), 2);
+        assert_eq!(parsed.data[0].registration_status, RegistrationStatus::Registered);
+        assert_eq!(parsed.data[1].registration_status, RegistrationStatus::Unknown);
     }
 }
\ No newline at end of file
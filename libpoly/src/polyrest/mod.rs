@@ -1,42 +1,179 @@
 //! Handlers for the Polycom REST API.
 
-use reqwest::{blocking, Method};
-use crate::errors::PolyRestError;
+use std::sync::Arc;
+use reqwest::{header::CONTENT_TYPE, Client, Method, StatusCode};
+use serde::{Deserialize, Deserializer};
+use crate::{errors::{PolyRestError, ResponseContext}, transport::{HttpRequest, Transport}};
 
 pub mod mgmt;
+pub mod callctrl;
+pub mod calllog;
 /// An API handler for dealing with Polycom's REST API.
 /// 
 /// This API serves a number of control and management functions, allowing the user to place calls, set and get config, and state, etc.
 /// As of this writing, the API is incomplete.
 /// 
+/// A handler can be shared between tasks, for example behind an `Arc`:
 /// ```
+/// use std::sync::Arc;
 /// use libpoly::polyrest::PolyRest;
 /// 
-/// let mut handler = PolyRest::new("Polycom", "789", "https://192.168.1.9", true).unwrap();
-/// let info = handler.device_info().unwrap();
+/// # async fn run() -> Result<(), libpoly::errors::PolyRestError> {
+/// let handler = Arc::new(PolyRest::new("Polycom", "789", "https://192.168.1.9", true)?);
+/// let info = handler.device_info().await?;
 /// println!("device info: {:?}", info);
+/// # Ok(())
+/// # }
 /// ```
+#[derive(Clone)]
 pub struct PolyRest {
     username: String,
     password: String,
     url: String,
-    client: reqwest::blocking::Client
+    transport: Arc<dyn Transport>,
+    allow_unknown_params: bool
+}
+
+/// The Polycom status code returned on success
+pub const STATUS_SUCCESS: &str = "2000";
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "PascalCase")]
+/// The response returned by endpoints that only acknowledge a request, and carry no data.
+pub struct Acknowledgement {
+    /// The Polycom status code, "2000" on success
+    pub status: String,
 }
 
 impl PolyRest {
     /// Create a new PolyRest handler 
     pub fn new<S: Into<String>>(username: S, password: S, url: S, insecure: bool) -> Result<Self, PolyRestError> {
-        let client = blocking::Client::builder().danger_accept_invalid_certs(insecure).build()?;
-        Ok(Self { client, username: username.into(), password: password.into(), url: url.into()})
+        let client = Client::builder().danger_accept_invalid_certs(insecure).build()?;
+        Ok(Self::with_transport(username, password, url, client))
+    }
+
+    /// Create a new PolyRest handler that sends requests over the given [`Transport`]
+    pub fn with_transport<S: Into<String>, T: Transport + 'static>(username: S, password: S, url: S, transport: T) -> Self {
+        Self { transport: Arc::new(transport), username: username.into(), password: password.into(), url: url.into(), allow_unknown_params: false}
+    }
+
+    /// Allow `config_set` to send parameters that aren't in the [`crate::catalog`].
+    /// Values for known parameters are still validated.
+    pub fn with_allow_unknown_params(mut self, allow: bool) -> Self {
+        self.allow_unknown_params = allow;
+        self
+    }
+
+    async fn raw_get(&self, path: String) -> Result<String, PolyRestError> {
+        self.raw_request(Method::GET, path, None, true).await
+    }
+
+    async fn raw_post(&self, path: String, body: String) -> Result<String, PolyRestError> {
+        self.raw_request(Method::POST, path, Some(body), true).await
+    }
+
+    /// Send a request to the device and return the body.
+    /// If `check_status` is false, a Polycom `Status` code indicating failure is left to the caller to handle.
+    async fn raw_request(&self, method: Method, path: String, body: Option<String>, check_status: bool) -> Result<String, PolyRestError> {
+        let mut req = HttpRequest::new(method, &path).basic_auth(&self.username, &self.password)?;
+        if let Some(body) = body {
+            req = req.body(body).header(CONTENT_TYPE, "application/json")?;
+        }
+
+        let resp = self.transport.send(req).await?;
+        let resp_str = resp.body_str();
+        check_response(&path, resp.status, &resp_str, check_status)?;
+
+        Ok(resp_str)
+    }
+}
+
+#[derive(Deserialize)]
+struct StatusOnly {
+    #[serde(rename = "Status")]
+    status: Option<String>
+}
+
+/// Map a response from the device to an error, based on the HTTP status and the Polycom `Status` code in the body.
+fn check_response(url: &str, http_status: StatusCode, body: &str, check_status: bool) -> Result<(), PolyRestError> {
+    // a phone with the REST API turned off serves its web UI instead of JSON
+    if http_status.is_success() && body.trim_start().starts_with('<') {
+        return Err(PolyRestError::ApiDisabled(ResponseContext::new(url, http_status.as_u16(), None, body)))
+    }
+    let polycom_status = serde_json::from_str::<StatusOnly>(body).ok().and_then(|parsed| parsed.status);
+
+    let failed_code = polycom_status.as_deref().filter(|code| *code != STATUS_SUCCESS);
+    if http_status.is_success() && (failed_code.is_none() || !check_status) {
+        return Ok(())
     }
 
-    fn raw_get(&mut self, path: String) -> Result<String, PolyRestError> {
-        let req = self.client.request(Method::GET, path).basic_auth(self.username.clone(), Some(self.password.clone())).build()?;
-        let resp = self.client.execute(req)?.error_for_status()?;
-        let raw_resp = resp.bytes()?;   
-        
-        let resp_str = String::from_utf8_lossy(&raw_resp);
+    let code = failed_code.map(str::to_string);
+    let ctx = ResponseContext::new(url, http_status.as_u16(), code.clone(), body);
+    let err = match (http_status, code.as_deref()) {
+        (StatusCode::UNAUTHORIZED, _) => PolyRestError::AuthError(ctx),
+        (StatusCode::FORBIDDEN, _) => PolyRestError::ApiDisabled(ctx),
+        (_, Some("4001")) | (StatusCode::SERVICE_UNAVAILABLE, _) => PolyRestError::DeviceBusy(ctx),
+        (_, Some("4004")) | (StatusCode::NOT_FOUND | StatusCode::METHOD_NOT_ALLOWED, _) => PolyRestError::UnsupportedEndpoint(ctx),
+        (_, Some("4000" | "4005" | "4007" | "4009")) | (StatusCode::BAD_REQUEST, _) => PolyRestError::InvalidParameter(ctx),
+        _ => PolyRestError::ApiError(ctx)
+    };
+    Err(err)
+}
+
+/// Deserialize a numeric value that the phone reports as a string, e.g. `"DurationInSeconds": "42"`
+pub(crate) fn from_str_num<'de, D: Deserializer<'de>, T: std::str::FromStr>(deserializer: D) -> Result<T, D::Error>
+where T::Err: std::fmt::Display {
+    let raw = String::deserialize(deserializer)?;
+    raw.trim().parse().map_err(serde::de::Error::custom)
+}
 
-        Ok(resp_str.to_string())   
+/// Like [`from_str_num`], for values that only some firmware reports. Missing and empty values become `None`.
+pub(crate) fn from_opt_str_num<'de, D: Deserializer<'de>, T: std::str::FromStr>(deserializer: D) -> Result<Option<T>, D::Error>
+where T::Err: std::fmt::Display {
+    match Option::<String>::deserialize(deserializer)? {
+        Some(raw) if !raw.trim().is_empty() => raw.trim().parse().map(Some).map_err(serde::de::Error::custom),
+        _ => Ok(None)
     }
-}
\ No newline at end of file
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classify_responses() {
+        let url = "https://phone/api/v1/mgmt/device/info";
+        assert!(check_response(url, StatusCode::OK, r#"{"Status": "2000"}"#, true).is_ok());
+        assert!(matches!(check_response(url, StatusCode::OK, "<html>login</html>", true), Err(PolyRestError::ApiDisabled(_))));
+        assert!(matches!(check_response(url, StatusCode::UNAUTHORIZED, "", true), Err(PolyRestError::AuthError(_))));
+        assert!(matches!(check_response(url, StatusCode::NOT_FOUND, "", true), Err(PolyRestError::UnsupportedEndpoint(_))));
+
+        let err = check_response(url, StatusCode::OK, r#"{"Status": "4001"}"#, true).unwrap_err();
+        let PolyRestError::DeviceBusy(ctx) = err else { panic!("expected DeviceBusy, got {:?}", err) };
+        assert_eq!(ctx.polycom_status.as_deref(), Some("4001"));
+        assert_eq!(ctx.http_status, 200);
+
+        assert!(check_response(url, StatusCode::OK, r#"{"Status": "4000"}"#, false).is_ok());
+    }
+
+    #[test]
+    fn handles_are_send_sync() {
+        fn assert_send_sync<T: Send + Sync>(_: &T) {}
+        fn assert_send<T: Send>(_: T) {}
+        let handler = PolyRest::new("Polycom", "789", "https://192.168.1.9", true).unwrap();
+        assert_send_sync(&handler);
+        assert_send(handler.config_set([("reg.1.label", "Desk")]));
+        assert_send(handler.start_consultative_transfer("0x1".parse().unwrap(), "1002".to_string(), 1, callctrl::DialType::Tel));
+
+        let push = crate::push::PushMessenger::new("Push", "Push", "https://192.168.1.9", true).unwrap();
+        assert_send_sync(&push);
+        assert_send(push.send(crate::push::MessageLevel::Normal, "hi", crate::push::PushType::HTML));
+    }
+
+    #[test]
+    fn truncate_body() {
+        let ctx = ResponseContext::new("url", 500, None, &"x".repeat(1000));
+        assert!(ctx.body.len() < 300);
+    }
+}
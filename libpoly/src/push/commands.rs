@@ -0,0 +1,328 @@
+//! Typed versions of the internal URIs the phone runs when sent with [`super::PushType::Command`].
+
+use std::{fmt::Display, str::FromStr};
+use clap::builder::PossibleValue;
+use crate::{errors::PolyRestError, suggest::closest_match};
+
+/// The number of line keys a phone can have
+const MAX_LINE_KEYS: u8 = 48;
+/// The number of soft keys along the bottom of the screen
+const MAX_SOFT_KEYS: u8 = 5;
+
+/// A key on the phone, as named by the `Key:` URI.
+///
+/// Key names are case-sensitive on the phone; `Key:home` is accepted and silently ignored,
+/// so parsing a name that isn't listed here fails with a suggestion instead.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Key {
+    /// A line key, from 1 to 48
+    Line(u8),
+    /// A digit on the dial pad
+    DialPad(u8),
+    DialPadStar,
+    DialPadPound,
+    /// A soft key under the screen, from 1 to 5
+    Softkey(u8),
+    VolUp,
+    VolDown,
+    Headset,
+    Handsfree,
+    MicMute,
+    Home,
+    Menu,
+    Setup,
+    Messages,
+    Directories,
+    Applications,
+    DoNotDisturb,
+    ArrowUp,
+    ArrowDown,
+    ArrowLeft,
+    ArrowRight,
+    Select,
+    Backspace,
+    Conference,
+    Transfer,
+    Redial,
+    Hold,
+    CallList,
+    Status,
+}
+
+/// Keys with a fixed name
+const NAMED_KEYS: &[Key] = &[
+    Key::DialPadStar, Key::DialPadPound, Key::VolUp, Key::VolDown, Key::Headset, Key::Handsfree, Key::MicMute, Key::Home, Key::Menu,
+    Key::Setup, Key::Messages, Key::Directories, Key::Applications, Key::DoNotDisturb, Key::ArrowUp, Key::ArrowDown, Key::ArrowLeft,
+    Key::ArrowRight, Key::Select, Key::Backspace, Key::Conference, Key::Transfer, Key::Redial, Key::Hold, Key::CallList, Key::Status,
+];
+
+impl Key {
+    /// check that a numbered key exists on the phone; the phone silently ignores keys it doesn't have
+    pub fn validate(&self) -> Result<(), PolyRestError> {
+        let (num, range) = match self {
+            Key::Line(num) => (num, 1..=MAX_LINE_KEYS),
+            Key::DialPad(num) => (num, 0..=9),
+            Key::Softkey(num) => (num, 1..=MAX_SOFT_KEYS),
+            _ => return Ok(())
+        };
+        if range.contains(num) {
+            return Ok(())
+        }
+        Err(unknown_key(&self.to_string()))
+    }
+
+    /// every valid key name
+    pub fn names() -> Vec<String> {
+        let numbered = (1..=MAX_LINE_KEYS).map(Key::Line)
+            .chain((0..=9).map(Key::DialPad))
+            .chain((1..=MAX_SOFT_KEYS).map(Key::Softkey));
+        numbered.map(|key| key.to_string())
+            .chain(NAMED_KEYS.iter().map(|key| key.to_string()))
+            .collect()
+    }
+}
+
+impl Display for Key {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Key::Line(num) => write!(f, "Line{}", num),
+            Key::DialPad(num) => write!(f, "DialPad{}", num),
+            Key::Softkey(num) => write!(f, "Softkey{}", num),
+            Key::DialPadStar => write!(f, "DialPadStar"),
+            Key::DialPadPound => write!(f, "DialPadPound"),
+            Key::VolUp => write!(f, "VolUp"),
+            Key::VolDown => write!(f, "VolDown"),
+            Key::Headset => write!(f, "Headset"),
+            Key::Handsfree => write!(f, "Handsfree"),
+            Key::MicMute => write!(f, "MicMute"),
+            Key::Home => write!(f, "Home"),
+            Key::Menu => write!(f, "Menu"),
+            Key::Setup => write!(f, "Setup"),
+            Key::Messages => write!(f, "Messages"),
+            Key::Directories => write!(f, "Directories"),
+            Key::Applications => write!(f, "Applications"),
+            Key::DoNotDisturb => write!(f, "DoNotDisturb"),
+            Key::ArrowUp => write!(f, "ArrowUp"),
+            Key::ArrowDown => write!(f, "ArrowDown"),
+            Key::ArrowLeft => write!(f, "ArrowLeft"),
+            Key::ArrowRight => write!(f, "ArrowRight"),
+            Key::Select => write!(f, "Select"),
+            Key::Backspace => write!(f, "Backspace"),
+            Key::Conference => write!(f, "Conference"),
+            Key::Transfer => write!(f, "Transfer"),
+            Key::Redial => write!(f, "Redial"),
+            Key::Hold => write!(f, "Hold"),
+            Key::CallList => write!(f, "CallList"),
+            Key::Status => write!(f, "Status"),
+        }
+    }
+}
+
+impl FromStr for Key {
+    type Err = PolyRestError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(key) = NAMED_KEYS.iter().find(|key| key.to_string() == s) {
+            return Ok(*key)
+        }
+        let numbered = [("Line", Key::Line as fn(u8) -> Key), ("DialPad", Key::DialPad), ("Softkey", Key::Softkey)];
+        for (prefix, variant) in numbered {
+            let key = s.strip_prefix(prefix).and_then(|num| num.parse::<u8>().ok()).map(variant);
+            if let Some(key) = key.filter(|key| key.validate().is_ok()) {
+                return Ok(key)
+            }
+        }
+        Err(unknown_key(s))
+    }
+}
+
+fn unknown_key(name: &str) -> PolyRestError {
+    let names = Key::names();
+    let suggestion = closest_match(name, names.iter().map(String::as_str)).map(str::to_string);
+    PolyRestError::UnknownKey { key: name.to_string(), suggestion }
+}
+
+/// An action for the phone's browser, as named by the `SoftKey:` URI
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SoftKeyAction {
+    /// Go back to the previous page
+    Back,
+    /// Cancel the current action
+    Cancel,
+    /// Close the browser
+    Exit,
+    /// Go to the browser's home page
+    Home,
+    /// Reload the current page
+    Refresh,
+    /// Reset the fields on the current page
+    Reset,
+    /// Submit the form on the current page
+    Submit,
+}
+
+impl clap::ValueEnum for SoftKeyAction {
+    fn value_variants<'a>() -> &'a [Self] {
+        &[Self::Back, Self::Cancel, Self::Exit, Self::Home, Self::Refresh, Self::Reset, Self::Submit]
+    }
+
+    fn to_possible_value(&self) -> Option<clap::builder::PossibleValue> {
+        match self {
+            SoftKeyAction::Back => Some(PossibleValue::new("back")),
+            SoftKeyAction::Cancel => Some(PossibleValue::new("cancel")),
+            SoftKeyAction::Exit => Some(PossibleValue::new("exit")),
+            SoftKeyAction::Home => Some(PossibleValue::new("home")),
+            SoftKeyAction::Refresh => Some(PossibleValue::new("refresh")),
+            SoftKeyAction::Reset => Some(PossibleValue::new("reset")),
+            SoftKeyAction::Submit => Some(PossibleValue::new("submit")),
+        }
+    }
+}
+
+impl Display for SoftKeyAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SoftKeyAction::Back => write!(f, "Back"),
+            SoftKeyAction::Cancel => write!(f, "Cancel"),
+            SoftKeyAction::Exit => write!(f, "Exit"),
+            SoftKeyAction::Home => write!(f, "Home"),
+            SoftKeyAction::Refresh => write!(f, "Refresh"),
+            SoftKeyAction::Reset => write!(f, "Reset"),
+            SoftKeyAction::Submit => write!(f, "Submit"),
+        }
+    }
+}
+
+/// An internal URI the phone runs when it's pushed as a command.
+///
+/// Several commands can be sent in one push with [`super::PushMessenger::send_commands`]; the phone runs them in order.
+/// Commands can also be parsed from their URI form:
+/// ```
+/// use libpoly::push::{Key, PushCommand};
+///
+/// let cmd: PushCommand = "Key:Line2".parse().unwrap();
+/// assert_eq!(cmd, PushCommand::Key(Key::Line(2)));
+/// assert!("Key:home".parse::<PushCommand>().unwrap_err().to_string().contains("did you mean Home?"));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PushCommand {
+    /// Press a key, e.g. `Key:Home`
+    Key(Key),
+    /// Dial a number, optionally on a given line, e.g. `tel:\5551234;2`. Numbers can only contain digits, `*`, `#` and `+`.
+    Dial { number: String, line: Option<u8> },
+    /// Run a browser soft key action, e.g. `SoftKey:Back`
+    SoftKey(SoftKeyAction),
+    /// Load a page in the phone's browser, e.g. `SoftKey:Fetch;http://server/page.xhtml`
+    Fetch(String),
+}
+
+impl PushCommand {
+    /// check the command before it's sent; commands are sent as-is, so a stray newline in a number would run a second command
+    pub fn validate(&self) -> Result<(), PolyRestError> {
+        match self {
+            PushCommand::Key(key) => key.validate(),
+            PushCommand::Dial { number, line } => {
+                let dialable = |c: char| c.is_ascii_digit() || matches!(c, '*' | '#' | '+');
+                if number.is_empty() || !number.chars().all(dialable) {
+                    return Err(PolyRestError::InvalidPushCommand(format!("{:?} is not a number; use only digits, *, # and +", number)))
+                }
+                match line {
+                    Some(line) if !(1..=MAX_LINE_KEYS).contains(line) => Err(PolyRestError::InvalidPushCommand(format!("no line {}; lines run from 1 to {}", line, MAX_LINE_KEYS))),
+                    _ => Ok(())
+                }
+            },
+            PushCommand::SoftKey(_) => Ok(()),
+            PushCommand::Fetch(url) => {
+                // the body isn't escaped, so anything that would break the XML is refused rather than sent
+                let web = url.starts_with("http://") || url.starts_with("https://");
+                if !web || url.chars().any(|c| c.is_whitespace() || c.is_control() || matches!(c, '<' | '>' | '&')) {
+                    return Err(PolyRestError::InvalidPushCommand(format!("{:?} is not an http(s) URL without spaces, <, > or &", url)))
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl Display for PushCommand {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PushCommand::Key(key) => write!(f, "Key:{}", key),
+            PushCommand::Dial { number, line: None } => write!(f, "tel:\\{}", number),
+            PushCommand::Dial { number, line: Some(line) } => write!(f, "tel:\\{};{}", number, line),
+            PushCommand::SoftKey(action) => write!(f, "SoftKey:{}", action),
+            PushCommand::Fetch(url) => write!(f, "SoftKey:Fetch;{}", url),
+        }
+    }
+}
+
+impl FromStr for PushCommand {
+    type Err = PolyRestError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(key) = s.strip_prefix("Key:") {
+            return Ok(PushCommand::Key(key.parse()?))
+        }
+        if let Some(number) = s.strip_prefix("tel:") {
+            let number = number.trim_start_matches('\\');
+            let cmd = match number.split_once(';') {
+                Some((number, line)) => {
+                    let line = line.parse().map_err(|_| PolyRestError::InvalidPushCommand(format!("{}: line must be a number", s)))?;
+                    PushCommand::Dial { number: number.to_string(), line: Some(line) }
+                },
+                None => PushCommand::Dial { number: number.to_string(), line: None }
+            };
+            cmd.validate()?;
+            return Ok(cmd)
+        }
+        if let Some(url) = s.strip_prefix("SoftKey:Fetch;") {
+            let cmd = PushCommand::Fetch(url.to_string());
+            cmd.validate()?;
+            return Ok(cmd)
+        }
+        if let Some(action) = s.strip_prefix("SoftKey:") {
+            return <SoftKeyAction as clap::ValueEnum>::from_str(action, true)
+                .map(PushCommand::SoftKey)
+                .map_err(|_| PolyRestError::InvalidPushCommand(s.to_string()))
+        }
+        Err(PolyRestError::InvalidPushCommand(s.to_string()))
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn key_names() {
+        for name in Key::names() {
+            assert_eq!(name.parse::<Key>().unwrap().to_string(), name);
+        }
+        assert_eq!("DialPad0".parse::<Key>().unwrap(), Key::DialPad(0));
+        assert_eq!("Status".parse::<Key>().unwrap(), Key::Status);
+        assert!(matches!("Line0".parse::<Key>(), Err(PolyRestError::UnknownKey { .. })));
+        assert!(matches!("Line49".parse::<Key>(), Err(PolyRestError::UnknownKey { .. })));
+        assert!(matches!(Key::Softkey(6).validate(), Err(PolyRestError::UnknownKey { .. })));
+        assert!(Key::DialPad(9).validate().is_ok());
+        match "volup".parse::<Key>() {
+            Err(PolyRestError::UnknownKey { suggestion, .. }) => assert_eq!(suggestion.as_deref(), Some("VolUp")),
+            other => panic!("expected an unknown key, got {:?}", other)
+        }
+    }
+
+    #[test]
+    fn command_uris() {
+        for uri in ["Key:DialPadPound", "Key:Status", "tel:\\5551234", "tel:\\5551234;2", "SoftKey:Back", "SoftKey:Home", "SoftKey:Fetch;http://server/menu.xhtml"] {
+            assert_eq!(uri.parse::<PushCommand>().unwrap().to_string(), uri);
+        }
+        assert_eq!("tel:5551234".parse::<PushCommand>().unwrap(), PushCommand::Dial { number: "5551234".to_string(), line: None });
+        assert!(matches!("SoftKey:Forward".parse::<PushCommand>(), Err(PolyRestError::InvalidPushCommand(_))));
+        for uri in ["tel:", "tel:555\nKey:Home", "tel:555<1>", "tel:a&b", "tel:555;0", "tel:555;49", "tel:555;two", "tel:;1",
+            "SoftKey:Fetch;", "SoftKey:Fetch;ftp://server", "SoftKey:Fetch;http://server/a?b=1&c=2"] {
+            assert!(matches!(uri.parse::<PushCommand>(), Err(PolyRestError::InvalidPushCommand(_))), "{}", uri);
+        }
+        assert!(PushCommand::Dial { number: "*72+15551234#".to_string(), line: Some(48) }.validate().is_ok());
+        assert!(matches!("Home".parse::<PushCommand>(), Err(PolyRestError::InvalidPushCommand(_))));
+    }
+}
@@ -1,52 +1,221 @@
-use digest_auth::AuthContext;
-use reqwest::{blocking, header::{AUTHORIZATION, CONTENT_TYPE}, Method};
+use digest_auth::{AuthContext, WwwAuthenticateHeader};
+use std::sync::{Arc, Mutex};
+use reqwest::{header::{AUTHORIZATION, CONTENT_TYPE, WWW_AUTHENTICATE}, Client, Method, StatusCode};
 
-use crate::{errors::PolyRestError, push::{MessageData, PolycomIPPhone}};
+use crate::{errors::{PolyRestError, ResponseContext}, push::{MessageData, PolycomIPPhone}, transport::{HttpRequest, HttpResponse, Transport}};
 
-use super::{MessageLevel, PushMessenger, PushType};
+use super::{MessageLevel, PushCommand, PushMessenger, PushResult, PushType};
 
 
 impl PushMessenger {
     /// Create a new push messenger
     /// Note that the push API credentials are often different from the REST API credentials.
     pub fn new<S: Into<String>>(username: S, password: S, url: S, insecure: bool) -> Result<Self, PolyRestError> {
-        let client = blocking::Client::builder().danger_accept_invalid_certs(insecure).build()?;
-        Ok(Self { client, username: username.into(), password: password.into(), url: url.into()})
+        let client = Client::builder().danger_accept_invalid_certs(insecure).build()?;
+        Ok(Self::with_transport(username, password, url, client))
+    }
+
+    /// Create a new push messenger that sends requests over the given [`Transport`]
+    pub fn with_transport<S: Into<String>, T: Transport + 'static>(username: S, password: S, url: S, transport: T) -> Self {
+        Self { transport: Arc::new(transport), username: username.into(), password: password.into(), url: url.into(), challenge: Arc::new(Mutex::new(None))}
     }
 
     /// Send a one-time message
     /// The message_body will be inserted into the Data object of the XML push body.
-    pub fn send<S: Into<String>>(&mut self, level: MessageLevel, message_body: S, cmd_type: PushType) -> Result<String, PolyRestError> {
+    /// Returns an error only if the push couldn't be delivered; check the [`PushResult`] to see if the phone accepted it.
+    pub async fn send<S: Into<String>>(&self, level: MessageLevel, message_body: S, cmd_type: PushType) -> Result<PushResult, PolyRestError> {
         let payload = PolycomIPPhone {data: MessageData{priority: level, body: message_body.into()}};
-        self.send_message_payload(payload, cmd_type)
+        self.send_message_payload(payload, cmd_type).await
     } 
 
+    /// Send one or more internal URIs, such as key presses, in a single push.
+    /// Nothing is sent if any command fails [`PushCommand::validate`].
+    pub async fn send_commands(&self, level: MessageLevel, commands: &[PushCommand]) -> Result<PushResult, PolyRestError> {
+        if commands.is_empty() {
+            return Err(PolyRestError::InvalidPushCommand("no commands given".to_string()))
+        }
+        for cmd in commands {
+            cmd.validate()?;
+        }
+        let body = commands.iter().map(PushCommand::to_string).collect::<Vec<_>>().join("\n");
+        self.send(level, body, PushType::Command).await
+    }
+
     // here be dragons
-    fn send_message_payload(&mut self, payload: PolycomIPPhone, command_type: PushType) -> Result<String, PolyRestError> {
+    async fn send_message_payload(&self, payload: PolycomIPPhone, command_type: PushType) -> Result<PushResult, PolyRestError> {
         let str_payload = quick_xml::se::to_string(&payload)?;
         //unfuck XML
         let unescaped = quick_xml::escape::unescape(&str_payload)?.to_string();
         let path = format!("{}/push", self.url);
         // for whatever reason, the push endpoint requires digest auth, while  the regular rest API doesn't, so go through the digest auth steps.
-        //  the Polycom API server is....particular. Do this in some way it doesn't like and it'll just return a 200 and silently fail.
-        let test_req = self.client.request(Method::POST, path.clone()).header(CONTENT_TYPE, "application/x-www-form-urlencoded").build()?;
-        let test_resp = self.client.execute(test_req)?;
+        // The challenge is cached, so only the first message (or one sent after the nonce goes stale) pays for the extra round trip.
+        let mut stale_retries = 1;
+        loop {
+            let Some(authorization) = self.authorize(&path, &unescaped)? else {
+                let prompt = self.request_challenge(&path).await?;
+                *self.challenge.lock().unwrap() = Some(prompt);
+                continue
+            };
 
-        // fetch the auth headers from the first response
-        let www_auth = test_resp.headers().get("www-authenticate").unwrap();
-        let context = AuthContext::new_with_method(&self.username, &self.password, path.clone(), Some(unescaped.as_bytes()), digest_auth::HttpMethod::from("POST"));
-        let mut prompt = digest_auth::parse(www_auth.to_str().unwrap()).unwrap();
-        let new_headers = prompt.respond(&context).unwrap();
+            let req = HttpRequest::new(Method::POST, path.clone())
+            .body(unescaped.clone()).header(CONTENT_TYPE, command_type.clone())?.header(AUTHORIZATION, authorization)?;
+            let resp = self.transport.send(req).await?;
 
-        // send the second request
-        let second_req = self.client.request(Method::POST, path.clone())
-        .body(unescaped).header(CONTENT_TYPE, command_type).header(AUTHORIZATION, new_headers.to_header_string()).build()?;
-        let second_resp = self.client.execute(second_req)?.error_for_status()?; 
+            let resp_str = resp.body_str();
+            let ctx = ResponseContext::new(&path, resp.status.as_u16(), None, &resp_str);
+            match resp.status {
+                status if status.is_success() => return Ok(PushResult::from_body(&resp_str)),
+                StatusCode::UNAUTHORIZED => {
+                    // a stale nonce just needs the new challenge the device sent back; anything else means the credentials are wrong
+                    let fresh = parse_challenge(&path, &resp).ok().filter(|prompt| prompt.stale);
+                    let retry = fresh.is_some() && stale_retries > 0;
+                    *self.challenge.lock().unwrap() = fresh;
+                    if !retry {
+                        return Ok(PushResult::AuthFailed)
+                    }
+                    stale_retries -= 1;
+                },
+                StatusCode::FORBIDDEN => return Err(PolyRestError::PushDisabled(ctx)),
+                _ => return Err(PolyRestError::ApiError(ctx))
+            }
+        }
+    }
 
-        let raw_resp = second_resp.bytes()?;   
-        let resp_str = String::from_utf8_lossy(&raw_resp).to_string();
+    /// Answer the cached challenge, bumping its nonce count. Returns None if there is no cached challenge.
+    fn authorize(&self, path: &str, body: &str) -> Result<Option<String>, PolyRestError> {
+        let mut cached = self.challenge.lock().unwrap();
+        let Some(prompt) = cached.as_mut() else { return Ok(None) };
+        let context = AuthContext::new_with_method(&self.username, &self.password, path, Some(body.as_bytes()), digest_auth::HttpMethod::from("POST"));
+        let authorization = prompt.respond(&context).map_err(|err| PolyRestError::InvalidChallenge(err.to_string()))?;
+        Ok(Some(authorization.to_header_string()))
+    }
 
-        Ok(resp_str)
+    /// Send an unauthenticated request to get a fresh digest challenge
+    async fn request_challenge(&self, path: &str) -> Result<WwwAuthenticateHeader, PolyRestError> {
+        //  the Polycom API server is....particular. Do this in some way it doesn't like and it'll just return a 200 and silently fail.
+        let req = HttpRequest::new(Method::POST, path).header(CONTENT_TYPE, "application/x-www-form-urlencoded")?;
+        let resp = self.transport.send(req).await?;
+        if resp.status == StatusCode::FORBIDDEN {
+            return Err(PolyRestError::PushDisabled(ResponseContext::new(path, resp.status.as_u16(), None, &resp.body_str())))
+        }
+        parse_challenge(path, &resp)
     }
 }
 
+/// Fetch the digest challenge from a response's WWW-Authenticate header
+fn parse_challenge(path: &str, resp: &HttpResponse) -> Result<WwwAuthenticateHeader, PolyRestError> {
+    let Some(www_auth) = resp.headers.get(WWW_AUTHENTICATE) else {
+        return Err(PolyRestError::MissingChallenge(ResponseContext::new(path, resp.status.as_u16(), None, &resp.body_str())))
+    };
+    let www_auth = www_auth.to_str().map_err(|err| PolyRestError::InvalidChallenge(err.to_string()))?;
+    digest_auth::parse(www_auth).map_err(|err| PolyRestError::InvalidChallenge(format!("{}: {}", err, www_auth)))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use crate::{push::{Key, MessageLevel, PushMessenger, PushRejection, PushType}, transport::MockTransport};
+    use super::*;
+
+    /// Emulate the push endpoint: challenge unauthenticated requests, and send a stale challenge
+    /// when the nonce doesn't match the current generation
+    fn push_endpoint(mock: &MockTransport, generation: Arc<AtomicU32>) {
+        mock.respond_with(Method::POST, "/push", move |req| {
+            let nonce = format!("nonce{}", generation.load(Ordering::SeqCst));
+            let www_auth = |stale: bool| format!("Digest realm=\"PPHONE\", qop=\"auth\", nonce=\"{}\"{}", nonce, if stale { ", stale=true" } else { "" });
+            match req.headers.get(AUTHORIZATION).map(|auth| auth.to_str().unwrap().to_string()) {
+                None => HttpResponse::new(StatusCode::UNAUTHORIZED, "").header(WWW_AUTHENTICATE, www_auth(false)).unwrap(),
+                Some(auth) if auth.contains(&format!("nonce=\"{}\"", nonce)) => HttpResponse::new(StatusCode::OK, "Push Message will be displayed successfully"),
+                Some(_) => HttpResponse::new(StatusCode::UNAUTHORIZED, "").header(WWW_AUTHENTICATE, www_auth(true)).unwrap(),
+            }
+        });
+    }
+
+    #[tokio::test]
+    async fn nonce_reuse() {
+        let mock = Arc::new(MockTransport::new());
+        let generation = Arc::new(AtomicU32::new(1));
+        push_endpoint(&mock, generation.clone());
+        let handle = PushMessenger::with_transport("Push", "Push", "https://phone", mock.clone());
+
+        handle.send(MessageLevel::Normal, "Key:Home", PushType::Command).await.unwrap();
+        handle.clone().send(MessageLevel::Normal, "Key:Home", PushType::Command).await.unwrap();
+        let requests = mock.requests();
+        assert_eq!(requests.len(), 3);
+        assert!(requests[2].headers[AUTHORIZATION].to_str().unwrap().contains("nc=00000002"));
+
+        // a stale nonce costs one retry with the new challenge
+        generation.store(2, Ordering::SeqCst);
+        handle.send(MessageLevel::Normal, "Key:Home", PushType::Command).await.unwrap();
+        handle.send(MessageLevel::Normal, "Key:Home", PushType::Command).await.unwrap();
+        let requests = mock.requests();
+        assert_eq!(requests.len(), 6);
+        assert!(requests[4].headers[AUTHORIZATION].to_str().unwrap().contains("nonce=\"nonce2\""));
+    }
+
+    #[tokio::test]
+    async fn several_commands() {
+        let mock = Arc::new(MockTransport::new());
+        push_endpoint(&mock, Arc::new(AtomicU32::new(1)));
+        let handle = PushMessenger::with_transport("Push", "Push", "https://phone", mock.clone());
+
+        let commands = [PushCommand::Key(Key::Line(2)), PushCommand::Dial { number: "5551234".to_string(), line: None }];
+        assert!(handle.send_commands(MessageLevel::Normal, &commands).await.unwrap().is_accepted());
+        let requests = mock.requests();
+        assert!(requests[1].body_str().contains(">Key:Line2\ntel:\\5551234</Data>"));
+        assert_eq!(requests[1].headers[CONTENT_TYPE], "application/x-com-polycom-spipx");
+        assert!(handle.send_commands(MessageLevel::Normal, &[]).await.is_err());
+
+        // invalid commands are caught before anything is sent
+        let sent = mock.requests().len();
+        assert!(matches!(handle.send_commands(MessageLevel::Normal, &[PushCommand::Key(Key::Line(49))]).await, Err(PolyRestError::UnknownKey { .. })));
+        let injected = PushCommand::Dial { number: "555\nKey:Home".to_string(), line: None };
+        assert!(matches!(handle.send_commands(MessageLevel::Normal, &[injected]).await, Err(PolyRestError::InvalidPushCommand(_))));
+        assert_eq!(mock.requests().len(), sent);
+    }
+
+    #[tokio::test]
+    async fn handshake_errors() {
+        let send = |mock: MockTransport| async move {
+            let handle = PushMessenger::with_transport("Push", "Push", "https://phone", mock);
+            handle.send(MessageLevel::Normal, "Key:Home", PushType::Command).await
+        };
+
+        let mock = MockTransport::new();
+        mock.respond(Method::POST, "/push", StatusCode::OK, "");
+        assert!(matches!(send(mock).await, Err(PolyRestError::MissingChallenge(_))));
+
+        let mock = MockTransport::new();
+        mock.respond_with(Method::POST, "/push", |_| HttpResponse::new(StatusCode::UNAUTHORIZED, "").header(WWW_AUTHENTICATE, "Digest qop=\"auth\"").unwrap());
+        assert!(matches!(send(mock).await, Err(PolyRestError::InvalidChallenge(_))));
+
+        let mock = MockTransport::new();
+        mock.respond(Method::POST, "/push", StatusCode::FORBIDDEN, "");
+        assert!(matches!(send(mock).await, Err(PolyRestError::PushDisabled(_))));
+
+        // a non-stale challenge in response to a digest means the credentials are wrong
+        let mock = MockTransport::new();
+        mock.respond_with(Method::POST, "/push", |_| HttpResponse::new(StatusCode::UNAUTHORIZED, "")
+            .header(WWW_AUTHENTICATE, "Digest realm=\"PPHONE\", qop=\"auth\", nonce=\"abc\"").unwrap());
+        assert_eq!(send(mock).await.unwrap(), PushResult::AuthFailed);
+    }
+
+    #[test]
+    fn classify_results() {
+        assert_eq!(PushResult::from_body("Push Message will be displayed successfully"), PushResult::Accepted);
+        assert_eq!(PushResult::from_body("Push Message will be displayed successfully\r\n"), PushResult::Accepted);
+        // a 200 with nothing in it is the silent failure, not a success
+        assert_eq!(PushResult::from_body(" \n"), PushResult::Rejected(PushRejection::Empty));
+        assert_eq!(PushResult::from_body("Error parsing XML"), PushResult::Rejected(PushRejection::BadXml));
+        assert_eq!(PushResult::from_body("Malformed request"), PushResult::Rejected(PushRejection::BadXml));
+        assert_eq!(PushResult::from_body("Higher priority message is displayed"), PushResult::Rejected(PushRejection::PriorityRefused));
+        assert_eq!(PushResult::from_body("Phone is busy"), PushResult::Rejected(PushRejection::Busy));
+        // only the exact accepted message counts, however successful the rest sounds
+        let unsuccessful = "Push Message was unsuccessful";
+        assert_eq!(PushResult::from_body(unsuccessful), PushResult::Rejected(PushRejection::Other(unsuccessful.to_string())));
+        assert!(!PushResult::from_body("Push Message will not be displayed successfully").is_accepted());
+        assert_eq!(PushResult::from_body(" <html>login</html>\n"), PushResult::Rejected(PushRejection::Other("<html>login</html>".to_string())));
+        assert!(!PushResult::AuthFailed.is_accepted());
+    }
+}
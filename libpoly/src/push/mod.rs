@@ -1,12 +1,17 @@
 //! Handlers for the /push API for sending HTML to the Polycom's built-in web browser.
 
-use std::fmt::Display;
+use std::{fmt::Display, sync::{Arc, Mutex}};
+use digest_auth::WwwAuthenticateHeader;
 use clap::builder::PossibleValue;
 use reqwest::header::{HeaderValue, InvalidHeaderValue};
 use serde::{Deserialize, Serialize};
+use crate::transport::Transport;
 
+mod commands;
 mod messages;
 
+pub use commands::{Key, PushCommand, SoftKeyAction};
+
 /// The type of push command to use.
 /// 
 /// This determines the Content-Type header sent to the /push API,
@@ -90,6 +95,92 @@ impl Display for MessageLevel {
     }
 }
 
+/// The outcome of a push request.
+///
+/// The phone answers pushes it can't display with a 200 as well, so the result comes from the status message in the response body.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PushResult {
+    /// The phone accepted the message
+    Accepted,
+    /// The phone received the message, but won't display or run it
+    Rejected(PushRejection),
+    /// The phone refused the push credentials
+    AuthFailed
+}
+
+/// Why the phone refused a push message.
+///
+/// Rejections are sorted by keywords in the status message; anything unrecognised is kept verbatim in [`PushRejection::Other`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum PushRejection {
+    /// The body wasn't a valid `PolycomIPPhone` XML message
+    BadXml,
+    /// A message with a higher priority is already being shown
+    PriorityRefused,
+    /// The phone is busy, for example restarting or in a menu
+    Busy,
+    /// The phone answered without a status message, which is how a silently dropped push looks
+    Empty,
+    /// Any other status message from the phone
+    Other(String)
+}
+
+/// The status message the phone returns when it accepts a push
+// got: "Push Message will be displayed successfully"
+const ACCEPTED_MESSAGE: &str = "Push Message will be displayed successfully";
+
+impl PushResult {
+    /// classify the status message the phone returns with a 200.
+    /// Only the exact accepted message counts as accepted, so an empty or unexpected reply is never mistaken for success.
+    pub(crate) fn from_body(body: &str) -> Self {
+        let msg = body.trim();
+        if msg.eq_ignore_ascii_case(ACCEPTED_MESSAGE) {
+            return PushResult::Accepted
+        }
+        let lower = msg.to_lowercase();
+        let reason = if msg.is_empty() {
+            PushRejection::Empty
+        } else if lower.contains("priority") {
+            PushRejection::PriorityRefused
+        } else if lower.contains("busy") {
+            PushRejection::Busy
+        } else if lower.contains("xml") || lower.contains("parse") || lower.contains("malformed") {
+            PushRejection::BadXml
+        } else {
+            PushRejection::Other(msg.to_string())
+        };
+        PushResult::Rejected(reason)
+    }
+
+    /// true if the phone accepted the message
+    pub fn is_accepted(&self) -> bool {
+        *self == PushResult::Accepted
+    }
+}
+
+impl Display for PushResult {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PushResult::Accepted => write!(f, "accepted"),
+            PushResult::Rejected(reason) => write!(f, "rejected: {}", reason),
+            PushResult::AuthFailed => write!(f, "authentication failed")
+        }
+    }
+}
+
+impl Display for PushRejection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PushRejection::BadXml => write!(f, "invalid XML"),
+            PushRejection::PriorityRefused => write!(f, "a higher priority message is showing"),
+            PushRejection::Busy => write!(f, "device is busy"),
+            PushRejection::Empty => write!(f, "no status message from device"),
+            PushRejection::Other(msg) => write!(f, "{}", msg)
+        }
+    }
+}
+
 
 #[derive(Deserialize, Serialize, Debug)]
 struct PolycomIPPhone {
@@ -116,12 +207,23 @@ struct MessageData {
 /// ```
 /// use libpoly::push::{PushMessenger, PushType, MessageLevel};
 /// 
-/// let mut handle = PushMessenger::init("Push", "Push", "https://192.168.1.9", true).unwrap();
-///handle.send(MessageLevel::Critical, "<h1>Silence Mortal, the VOIP phone is speaking </h1>", PushType::HTML).unwrap();
+/// # async fn run() -> Result<(), libpoly::errors::PolyRestError> {
+/// let handle = PushMessenger::new("Push", "Push", "https://192.168.1.9", true)?;
+/// let result = handle.send(MessageLevel::Critical, "<h1>Silence Mortal, the VOIP phone is speaking </h1>", PushType::HTML).await?;
+/// if !result.is_accepted() {
+///     println!("push failed: {}", result);
+/// }
+/// # Ok(())
+/// # }
 /// ```
+///
+/// The digest nonce from the device is cached, so sending several messages in a row with one handle (or its clones)
+/// only goes through the digest challenge once.
 pub struct PushMessenger {
     username: String,
     password: String,
     url: String,
-    client: reqwest::blocking::Client
+    transport: Arc<dyn Transport>,
+    /// The last digest challenge from the device, reused across sends until the nonce goes stale
+    challenge: Arc<Mutex<Option<WwwAuthenticateHeader>>>
 }
@@ -0,0 +1,41 @@
+//! "Did you mean" suggestions for mistyped names.
+
+/// The Levenshtein distance between two strings, ignoring ASCII case
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.to_ascii_lowercase().chars().collect();
+    let b: Vec<char> = b.to_ascii_lowercase().chars().collect();
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.iter().enumerate() {
+        let mut cur = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let cost = if ca == cb { 0 } else { 1 };
+            cur.push((prev[j] + cost).min(prev[j + 1] + 1).min(cur[j] + 1));
+        }
+        prev = cur;
+    }
+    prev[b.len()]
+}
+
+/// Find the candidate closest to `input`, if any is close enough to plausibly be a typo
+pub(crate) fn closest_match<'a, I: IntoIterator<Item = &'a str>>(input: &str, candidates: I) -> Option<&'a str> {
+    let max_distance = (input.len() / 3).max(2);
+    candidates.into_iter()
+        .map(|candidate| (edit_distance(input, candidate), candidate))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn suggestions() {
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(closest_match("home", ["Home", "Setup", "Menu"]), Some("Home"));
+        assert_eq!(closest_match("reg.1.adress", ["reg.1.address", "reg.1.label"]), Some("reg.1.address"));
+        assert_eq!(closest_match("zzzzzz", ["Home", "Setup"]), None);
+    }
+}
@@ -0,0 +1,193 @@
+//! The HTTP layer underneath [`crate::polyrest::PolyRest`] and [`crate::push::PushMessenger`].
+//!
+//! By default both clients send requests with a [`reqwest::Client`]. Anything that implements [`Transport`] can be used instead,
+//! such as a client with a proxy, custom TLS or instrumentation, or a [`MockTransport`] in tests:
+//! ```
+//! use std::sync::Arc;
+//! use libpoly::{polyrest::PolyRest, transport::MockTransport};
+//! use reqwest::{Method, StatusCode};
+//!
+//! # #[tokio::main(flavor = "current_thread")]
+//! # async fn main() -> Result<(), libpoly::errors::PolyRestError> {
+//! let mock = Arc::new(MockTransport::new());
+//! mock.respond(Method::POST, "/api/v1/mgmt/safeRestart", StatusCode::OK, r#"{"Status": "2000"}"#);
+//!
+//! let handler = PolyRest::with_transport("Polycom", "789", "https://phone", mock.clone());
+//! handler.safe_restart().await?;
+//! assert_eq!(mock.requests().len(), 1);
+//! # Ok(())
+//! # }
+//! ```
+
+use std::{future::Future, pin::Pin, sync::{Arc, Mutex}};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use reqwest::{header::{HeaderMap, HeaderName, HeaderValue, InvalidHeaderValue, AUTHORIZATION}, Method, StatusCode, Url};
+use crate::errors::PolyRestError;
+
+/// The future returned by [`Transport::send`]
+pub type TransportFuture<'a> = Pin<Box<dyn Future<Output = Result<HttpResponse, PolyRestError>> + Send + 'a>>;
+
+/// Sends a single HTTP request and returns the complete response.
+///
+/// Implementations should return responses with any HTTP status as-is; the clients map error statuses themselves.
+pub trait Transport: Send + Sync {
+    fn send(&self, req: HttpRequest) -> TransportFuture<'_>;
+}
+
+impl<T: Transport + ?Sized> Transport for Arc<T> {
+    fn send(&self, req: HttpRequest) -> TransportFuture<'_> {
+        (**self).send(req)
+    }
+}
+
+impl Transport for reqwest::Client {
+    fn send(&self, req: HttpRequest) -> TransportFuture<'_> {
+        Box::pin(async move {
+            let mut builder = self.request(req.method, req.url).headers(req.headers);
+            if let Some(body) = req.body {
+                builder = builder.body(body);
+            }
+            let resp = builder.send().await?;
+            let status = resp.status();
+            let headers = resp.headers().clone();
+            let body = resp.bytes().await?.to_vec();
+            Ok(HttpResponse { status, headers, body })
+        })
+    }
+}
+
+/// A request to be sent by a [`Transport`]
+#[derive(Debug, Clone)]
+pub struct HttpRequest {
+    pub method: Method,
+    pub url: String,
+    pub headers: HeaderMap,
+    pub body: Option<Vec<u8>>,
+}
+
+impl HttpRequest {
+    pub fn new<S: Into<String>>(method: Method, url: S) -> Self {
+        Self { method, url: url.into(), headers: HeaderMap::new(), body: None }
+    }
+
+    pub fn header<V: TryInto<HeaderValue, Error = InvalidHeaderValue>>(mut self, name: HeaderName, value: V) -> Result<Self, PolyRestError> {
+        self.headers.insert(name, value.try_into()?);
+        Ok(self)
+    }
+
+    pub fn basic_auth(self, username: &str, password: &str) -> Result<Self, PolyRestError> {
+        let encoded = STANDARD.encode(format!("{}:{}", username, password));
+        self.header(AUTHORIZATION, format!("Basic {}", encoded))
+    }
+
+    pub fn body<B: Into<Vec<u8>>>(mut self, body: B) -> Self {
+        self.body = Some(body.into());
+        self
+    }
+
+    /// The path component of the URL, or the whole URL if it can't be parsed
+    pub fn path(&self) -> String {
+        Url::parse(&self.url).map(|url| url.path().to_string()).unwrap_or_else(|_| self.url.clone())
+    }
+
+    /// The body as a string, replacing any invalid UTF-8
+    pub fn body_str(&self) -> String {
+        String::from_utf8_lossy(self.body.as_deref().unwrap_or_default()).to_string()
+    }
+}
+
+/// A complete response returned by a [`Transport`]
+#[derive(Debug, Clone)]
+pub struct HttpResponse {
+    pub status: StatusCode,
+    pub headers: HeaderMap,
+    pub body: Vec<u8>,
+}
+
+impl HttpResponse {
+    pub fn new<B: Into<Vec<u8>>>(status: StatusCode, body: B) -> Self {
+        Self { status, headers: HeaderMap::new(), body: body.into() }
+    }
+
+    pub fn header<V: TryInto<HeaderValue, Error = InvalidHeaderValue>>(mut self, name: HeaderName, value: V) -> Result<Self, PolyRestError> {
+        self.headers.insert(name, value.try_into()?);
+        Ok(self)
+    }
+
+    /// The body as a string, replacing any invalid UTF-8
+    pub fn body_str(&self) -> String {
+        String::from_utf8_lossy(&self.body).to_string()
+    }
+}
+
+type Responder = Box<dyn Fn(&HttpRequest) -> HttpResponse + Send + Sync>;
+
+/// An in-memory [`Transport`] that answers requests with canned responses, and records every request it receives.
+///
+/// Responses are matched on the method and URL path. Requests with no matching response get an empty 404.
+#[derive(Default)]
+pub struct MockTransport {
+    routes: Mutex<Vec<(Method, String, Responder)>>,
+    requests: Mutex<Vec<HttpRequest>>,
+}
+
+impl MockTransport {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Answer requests to the given path with a fixed status and body.
+    /// A later response for the same method and path replaces an earlier one.
+    pub fn respond<S: Into<String>>(&self, method: Method, path: &str, status: StatusCode, body: S) {
+        let body = body.into();
+        self.respond_with(method, path, move |_| HttpResponse::new(status, body.clone()));
+    }
+
+    /// Answer requests to the given path by calling `responder`, for responses that depend on the request
+    pub fn respond_with<F>(&self, method: Method, path: &str, responder: F)
+    where F: Fn(&HttpRequest) -> HttpResponse + Send + Sync + 'static {
+        self.routes.lock().unwrap().push((method, path.to_string(), Box::new(responder)));
+    }
+
+    /// Every request received so far, in order
+    pub fn requests(&self) -> Vec<HttpRequest> {
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+impl Transport for MockTransport {
+    fn send(&self, req: HttpRequest) -> TransportFuture<'_> {
+        let path = req.path();
+        let resp = self.routes.lock().unwrap().iter().rev()
+            .find(|(method, route, _)| *method == req.method && *route == path)
+            .map(|(_, _, responder)| responder(&req))
+            .unwrap_or_else(|| HttpResponse::new(StatusCode::NOT_FOUND, ""));
+        self.requests.lock().unwrap().push(req);
+        Box::pin(std::future::ready(Ok(resp)))
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn mock_routes() {
+        let mock = MockTransport::new();
+        mock.respond(Method::GET, "/api/v1/mgmt/device/info", StatusCode::OK, "first");
+        mock.respond(Method::GET, "/api/v1/mgmt/device/info", StatusCode::OK, "second");
+
+        let resp = mock.send(HttpRequest::new(Method::GET, "https://phone/api/v1/mgmt/device/info")).await.unwrap();
+        assert_eq!(resp.body_str(), "second");
+        let resp = mock.send(HttpRequest::new(Method::POST, "https://phone/api/v1/mgmt/device/info")).await.unwrap();
+        assert_eq!(resp.status, StatusCode::NOT_FOUND);
+        assert_eq!(mock.requests().len(), 2);
+    }
+
+    #[test]
+    fn basic_auth_header() {
+        let req = HttpRequest::new(Method::GET, "https://phone/").basic_auth("Polycom", "789").unwrap();
+        assert_eq!(req.headers[AUTHORIZATION], "Basic UG9seWNvbTo3ODk=");
+    }
+}
@@ -0,0 +1,270 @@
+use std::{collections::BTreeMap, fs::File, future::Future, io::Read, path::{Path, PathBuf}, time::{SystemTime, UNIX_EPOCH}};
+use anyhow::Context;
+use libpoly::{catalog, errors::PolyRestError, polyrest::{mgmt::ConfigResponseValue, PolyRest}};
+use serde::{Deserialize, Serialize};
+
+/// The archive layout version written by this release. Bump this when the layout changes.
+pub const BACKUP_VERSION: u32 = 1;
+
+const MANIFEST_FILE: &str = "manifest.json";
+const RUNNING_CONFIG_FILE: &str = "running_config.json";
+
+/// How many parameters to send per config_set request when restoring
+const RESTORE_BATCH_SIZE: usize = 100;
+
+/// Describes the device and archive layout of a backup
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Manifest {
+    pub version: u32,
+    /// Seconds since the unix epoch
+    pub created: u64,
+    pub mac_address: String,
+    pub model_number: String,
+    pub firmware_release: String,
+    /// Optional sections the device doesn't support, and the error it returned for each
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub skipped: BTreeMap<String, String>,
+}
+
+/// The parts of a backup needed to restore or compare it
+pub struct Backup {
+    pub manifest: Manifest,
+    pub running_config: BTreeMap<String, ConfigResponseValue>,
+}
+
+fn append_json<T: Serialize>(archive: &mut tar::Builder<File>, name: &str, value: &T) -> anyhow::Result<()> {
+    let data = serde_json::to_vec_pretty(value)?;
+    append_bytes(archive, name, &data)
+}
+
+fn append_bytes(archive: &mut tar::Builder<File>, name: &str, data: &[u8]) -> anyhow::Result<()> {
+    let mut header = tar::Header::new_gnu();
+    header.set_size(data.len() as u64);
+    header.set_mode(0o644);
+    header.set_mtime(SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs());
+    header.set_cksum();
+    archive.append_data(&mut header, name, data)?;
+    Ok(())
+}
+
+/// Fetch a section of the backup that not every firmware supports.
+/// If the device doesn't support it, the section is recorded in `skipped` instead of failing the backup.
+async fn optional_section<T, F>(name: &str, fetch: F, skipped: &mut BTreeMap<String, String>) -> anyhow::Result<Option<serde_json::Value>>
+where T: Serialize, F: Future<Output = Result<T, PolyRestError>> {
+    match fetch.await {
+        Ok(value) => Ok(Some(serde_json::to_value(value)?)),
+        Err(PolyRestError::UnsupportedEndpoint(ctx)) => {
+            eprintln!("skipping {}: {}", name, ctx);
+            skipped.insert(name.to_string(), ctx.to_string());
+            Ok(None)
+        },
+        Err(err) => Err(err.into())
+    }
+}
+
+/// Capture everything readable from the device into a tar archive.
+/// If `provision_root` is set, the device's directory and call list files are copied from the provisioning server as well.
+/// The archive is written next to `out` and only moved into place once the backup is complete.
+pub async fn run_backup(handler: &PolyRest, out: Option<String>, provision_root: Option<String>) -> anyhow::Result<()> {
+    let info = handler.device_info().await?;
+    let out = out.unwrap_or_else(|| format!("phone-{}.tar", info.mac_address));
+    let running_config = handler.running_config().await?;
+
+    let mut skipped = BTreeMap::new();
+    let sections = [
+        ("network_info.json", optional_section("network_info.json", handler.network_info(), &mut skipped).await?),
+        ("lines.json", optional_section("lines.json", handler.line_info(), &mut skipped).await?),
+        ("call_logs.json", optional_section("call_logs.json", handler.all_call_logs(), &mut skipped).await?),
+    ];
+
+    let manifest = Manifest {
+        version: BACKUP_VERSION,
+        created: SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs(),
+        mac_address: info.mac_address.clone(),
+        model_number: info.model_number.clone(),
+        firmware_release: info.firmware_release.clone(),
+        skipped,
+    };
+
+    let partial = format!("{}.partial", out);
+    let written = write_archive(&partial, |archive| {
+        append_json(archive, MANIFEST_FILE, &manifest)?;
+        append_json(archive, "device_info.json", &info)?;
+        append_json(archive, RUNNING_CONFIG_FILE, &running_config)?;
+        for (name, section) in &sections {
+            if let Some(section) = section {
+                append_json(archive, name, section)?;
+            }
+        }
+        if let Some(root) = &provision_root {
+            append_provisioned_files(archive, root, &info.mac_address)?;
+        }
+        Ok(())
+    });
+    if let Err(err) = written.and_then(|_| Ok(std::fs::rename(&partial, &out)?)) {
+        let _ = std::fs::remove_file(&partial);
+        return Err(err.context(format!("writing {}", out)))
+    }
+
+    println!("wrote backup of {} to {}", info.mac_address, out);
+    Ok(())
+}
+
+fn write_archive<F>(path: &str, fill: F) -> anyhow::Result<()>
+where F: FnOnce(&mut tar::Builder<File>) -> anyhow::Result<()> {
+    let mut archive = tar::Builder::new(File::create(path)?);
+    fill(&mut archive)?;
+    archive.finish()?;
+    Ok(())
+}
+
+/// Copy the device's directory and call list files from the provisioning server root
+fn append_provisioned_files(archive: &mut tar::Builder<File>, root: &str, mac_address: &str) -> anyhow::Result<()> {
+    // the directory and call lists aren't exposed over REST, but the phone uploads them to the provisioning server
+    for name in [format!("{}-directory.xml", mac_address), format!("{}-calls.xml", mac_address)] {
+        let path = PathBuf::from(root).join(&name);
+        if path.exists() {
+            archive.append_path_with_name(&path, format!("files/{}", name))?;
+        } else {
+            eprintln!("skipping {}: not found", path.to_string_lossy());
+        }
+    }
+    Ok(())
+}
+
+/// Read the manifest and running config from a backup archive
+pub fn read_backup<P: AsRef<Path>>(path: P) -> anyhow::Result<Backup> {
+    let path = path.as_ref();
+    let mut archive = tar::Archive::new(File::open(path).with_context(|| format!("opening {}", path.to_string_lossy()))?);
+    let mut manifest: Option<Manifest> = None;
+    let mut running_config = None;
+
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let name = entry.path()?.to_string_lossy().to_string();
+        let mut raw = String::new();
+        match name.as_str() {
+            MANIFEST_FILE => {
+                entry.read_to_string(&mut raw)?;
+                manifest = Some(serde_json::from_str(&raw)?);
+            },
+            RUNNING_CONFIG_FILE => {
+                entry.read_to_string(&mut raw)?;
+                running_config = Some(serde_json::from_str(&raw)?);
+            },
+            _ => {}
+        }
+    }
+
+    let manifest = manifest.context("backup has no manifest")?;
+    if manifest.version > BACKUP_VERSION {
+        anyhow::bail!("backup version {} is newer than this release supports ({})", manifest.version, BACKUP_VERSION)
+    }
+    let running_config = running_config.context("backup has no running config")?;
+    Ok(Backup { manifest, running_config })
+}
+
+/// Replay the non-default parameters from a backup onto the device
+pub async fn run_restore(handler: PolyRest, path: String, yes: bool) -> anyhow::Result<()> {
+    let backup = read_backup(&path)?;
+    let params: Vec<(String, String)> = backup.running_config.into_iter()
+        .filter(|(_, val)| val.source != "default")
+        .map(|(key, val)| (key, val.value))
+        .collect();
+    let total = params.len();
+
+    // the running config holds far more parameters than the catalog knows about, and they all came from a real device.
+    // Check the known ones up front, so a value the catalog disagrees with is reported instead of failing a batch halfway through the restore.
+    let mut failed = 0;
+    let mut changes = Vec::new();
+    for (key, value) in params {
+        match catalog::validate(&key, &value, true) {
+            Ok(()) => changes.push((key, value)),
+            Err(err) => {
+                eprintln!("skipping {}", err);
+                failed += 1;
+            }
+        }
+    }
+
+    let target = handler.device_info().await?;
+    let prompt = format!("Restore {} parameters from {} ({}, MAC {}) onto {} (MAC {})?",
+        changes.len(), path, backup.manifest.model_number, backup.manifest.mac_address, target.model_number, target.mac_address);
+    if !yes && !crate::confirm(&prompt)? {
+        return Ok(())
+    }
+
+    let handler = handler.with_allow_unknown_params(true);
+    for batch in changes.chunks(RESTORE_BATCH_SIZE) {
+        // report a failed batch per key and carry on, rather than leaving the rest of the device unrestored
+        let results = match handler.config_set(batch.to_vec()).await {
+            Ok(results) => results,
+            Err(err) => {
+                for (key, _) in batch {
+                    eprintln!("{}: {}", key, err);
+                }
+                failed += batch.len();
+                continue
+            }
+        };
+        for (key, status) in results.iter().filter(|(_, status)| !status.is_success()) {
+            eprintln!("{}: {}", key, status);
+            failed += 1;
+        }
+    }
+
+    println!("restored {} of {} parameters", total - failed, total);
+    if failed > 0 {
+        anyhow::bail!("{} parameters could not be restored", failed)
+    }
+    Ok(())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use libpoly::transport::MockTransport;
+    use axum::http::{Method, StatusCode};
+    use super::*;
+
+    #[test]
+    fn read_written_backup() {
+        let path = std::env::temp_dir().join(format!("polycli-backup-test-{}.tar", std::process::id()));
+        let manifest = Manifest { version: BACKUP_VERSION, created: 0, mac_address: "64167fcacee2".to_string(),
+            model_number: "VVX 411".to_string(), firmware_release: "5.5.0.22173".to_string(), skipped: BTreeMap::new() };
+        let config = BTreeMap::from([("reg.1.address".to_string(), ConfigResponseValue{value: "1001".to_string(), source: "configFile".to_string()})]);
+
+        let mut archive = tar::Builder::new(File::create(&path).unwrap());
+        append_json(&mut archive, MANIFEST_FILE, &manifest).unwrap();
+        append_json(&mut archive, RUNNING_CONFIG_FILE, &config).unwrap();
+        archive.finish().unwrap();
+        drop(archive);
+
+        let backup = read_backup(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(backup.manifest.mac_address, "64167fcacee2");
+        assert_eq!(backup.running_config["reg.1.address"].source, "configFile");
+    }
+
+    #[tokio::test]
+    async fn skip_unsupported_sections() {
+        let mock = std::sync::Arc::new(MockTransport::new());
+        mock.respond(Method::GET, "/api/v1/mgmt/device/info", StatusCode::OK, r#"{"data": {"ModelNumber": "VVX 411", "FirmwareRelease": "5.5.0.22173",
+        "DeviceType": "hardwareEndpoint", "MACAddress": "64167fcacee2", "DeviceVendor": "Polycom", "UpTimeSinceLastReboot": "0 Day 6:16:10",
+        "IPV4Address": "192.168.1.9", "IPV6Address": "::", "AttachedHardware": {"EM": []}}, "Status": "2000"}"#);
+        mock.respond(Method::GET, "/api/v1/mgmt/device/runningConfig", StatusCode::OK,
+            r#"{"data": {"reg.1.address": {"Value": "1001", "Source": "configFile"}}, "Status": "2000"}"#);
+        // everything else is a 404, like firmware without those endpoints
+        let handler = PolyRest::with_transport("Polycom", "789", "https://phone", mock);
+
+        let path = std::env::temp_dir().join(format!("polycli-backup-skip-{}.tar", std::process::id()));
+        let out = path.to_string_lossy().to_string();
+        run_backup(&handler, Some(out.clone()), None).await.unwrap();
+
+        let backup = read_backup(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert!(!Path::new(&format!("{}.partial", out)).exists());
+        assert_eq!(backup.running_config["reg.1.address"].value, "1001");
+        assert_eq!(backup.manifest.skipped.keys().collect::<Vec<_>>(), ["call_logs.json", "lines.json", "network_info.json"]);
+    }
+}
@@ -1,5 +1,7 @@
 use clap::{Parser, Subcommand};
-use libpoly::push::MessageLevel;
+use libpoly::{polyrest::{callctrl::{CallHandle, DialType}, calllog::CallLogKind, mgmt::TransferType}, push::{Key, MessageLevel, PushCommand, SoftKeyAction}};
+
+use crate::output::OutputFormat;
 
 
 #[derive(Debug, Parser)]
@@ -39,6 +41,33 @@ pub enum Commands {
         subcommand: PushSubcommands
     },
 
+    /// Compare and manage device configuration
+    Config {
+        #[clap(subcommand)]
+        subcommand: ConfigCommands
+    },
+
+    /// Save everything readable from the device into a tar archive
+    Backup {
+        /// The archive to write. Defaults to `phone-<mac>.tar`
+        #[arg(long="out", short='o')]
+        out: Option<String>,
+
+        /// The provisioning server root; if set, the device's directory and call list files are included from here
+        #[arg(long="provision-root")]
+        provision_root: Option<String>
+    },
+
+    /// Replay the non-default configuration from a backup archive onto the device
+    Restore {
+        /// The backup archive to restore
+        path: String,
+
+        /// Skip the confirmation prompt
+        #[arg(long, short='y')]
+        yes: bool
+    },
+
     /// Start up an HTTP file server server pointing to the specified path. If the Polycom device is configured to look for this server, it 
     /// will use the config files at the given path to configure the device.
     Provisioner {
@@ -49,10 +78,66 @@ pub enum Commands {
 
         /// The path to run the file server
         path: String
+    },
+
+    /// Emulate a VVX phone locally, serving the REST and push APIs. It listens on the host and port of --url,
+    /// and --user and --pass set the REST API credentials. Accepted push messages are logged, and listed as JSON at /sim/pushes.
+    Simulate {
+        /// The push API username
+        #[arg(long="push-user", default_value="Push")]
+        push_user: String,
+
+        /// The push API password
+        #[arg(long="push-pass", default_value="Push")]
+        push_pass: String
     }
 
 }
 
+#[derive(Debug, Subcommand)]
+pub enum ConfigCommands {
+    /// Print the parameters that differ between two configs.
+    /// Each config may be a device URL, `device` for the device given with --url, a backup archive (.tar), or a Polycom .cfg file
+    Diff {
+        left: String,
+        right: String,
+
+        /// Leave out parameters that are still set to their default value
+        #[arg(long="ignore-defaults")]
+        ignore_defaults: bool
+    },
+    /// Show the changes needed to bring devices in line with a desired-state file (.toml or .yaml).
+    /// If the file names no devices, the device given with --url is used
+    Plan {
+        path: String,
+
+        /// Allow parameters that aren't in the parameter catalog
+        #[arg(long="allow-unknown")]
+        allow_unknown: bool
+    },
+    /// Apply a desired-state file, then read the values back to verify them
+    Apply {
+        path: String,
+
+        /// Skip the confirmation prompt
+        #[arg(long, short='y')]
+        yes: bool,
+
+        /// Allow parameters that aren't in the parameter catalog
+        #[arg(long="allow-unknown")]
+        allow_unknown: bool
+    },
+    /// Describe a parameter from the parameter catalog
+    Describe {
+        #[arg(value_name="CONFIG_KEY")]
+        key: String
+    },
+    /// Search the parameter catalog by name or description
+    Search {
+        term: String
+    }
+}
+
 #[derive(Debug, Subcommand)]
 pub enum PushSubcommands {
     /// Send a command to the device
@@ -81,13 +166,32 @@ pub enum PushCmdSubcommands {
         /// String to send in the Data XML body
         msg: String
     },
-    /// Activate a key on the device
+    /// Press one or more keys on the device, in order
     Key{
-        key: String
+        /// Key names, e.g. Home, Line1, DialPad5 or VolUp
+        #[clap(required = true)]
+        keys: Vec<Key>
     },
     /// Dial a phone number
     Dial{
-        number: String
+        number: String,
+
+        /// The line to dial on
+        #[arg(long="line", short='l')]
+        line: Option<u8>
+    },
+    /// Run a soft key action in the phone's browser
+    SoftKey{
+        action: SoftKeyAction
+    },
+    /// Load a page in the phone's browser
+    Fetch{
+        url: String
+    },
+    /// Send several internal URIs in one push, e.g. Key:Line2 'tel:\5551234'
+    Uri{
+        #[clap(required = true)]
+        uris: Vec<PushCommand>
     }
 }
 
@@ -100,7 +204,127 @@ pub enum RestCommands {
         subcommand: MgmtCommands
     },
 
-    Ctrl
+    /// Run call control REST commands
+    Ctrl{
+        #[clap(subcommand)]
+        subcommand: CtrlCommands
+    }
+}
+
+#[derive(Debug, Subcommand)]
+pub enum CtrlCommands {
+    /// Print the state of all active calls
+    Status,
+    /// Dial a number
+    Dial {
+        /// The number or URI to dial
+        number: String,
+
+        /// The line to place the call on
+        #[arg(long="line", short='l', default_value_t=1)]
+        line: u32,
+
+        /// The type of call to place
+        #[arg(long="type", short='t', default_value_t = DialType::Tel, value_parser = clap::builder::EnumValueParser::<DialType>::new())]
+        dial_type: DialType
+    },
+    /// Answer an incoming call
+    Answer {
+        #[arg(value_name="CALL_REF")]
+        call_ref: CallHandle
+    },
+    /// Ignore an incoming call
+    Ignore {
+        #[arg(value_name="CALL_REF")]
+        call_ref: CallHandle
+    },
+    /// Reject an incoming call
+    Reject {
+        #[arg(value_name="CALL_REF")]
+        call_ref: CallHandle
+    },
+    /// End an active call
+    End {
+        #[arg(value_name="CALL_REF")]
+        call_ref: CallHandle
+    },
+    /// Place a call on hold
+    Hold {
+        #[arg(value_name="CALL_REF")]
+        call_ref: CallHandle
+    },
+    /// Resume a held call
+    Resume {
+        #[arg(value_name="CALL_REF")]
+        call_ref: CallHandle
+    },
+    /// Send DTMF digits on an active call. Use `,` to insert a two-second pause
+    Dtmf {
+        #[arg(value_name="CALL_REF")]
+        call_ref: CallHandle,
+
+        /// The digits to send, e.g. `1,,234#`
+        digits: String
+    },
+    /// Transfer a call
+    Transfer {
+        #[clap(subcommand)]
+        subcommand: TransferCommands
+    }
+}
+
+#[derive(Debug, Subcommand)]
+pub enum TransferCommands {
+    /// Transfer a call without consulting the target
+    Blind {
+        #[arg(value_name="CALL_REF")]
+        call_ref: CallHandle,
+
+        /// The number or URI to transfer to
+        dest: String
+    },
+    /// Hold a call and dial the transfer target. Prints the handle of the consult call
+    Start {
+        #[arg(value_name="CALL_REF")]
+        call_ref: CallHandle,
+
+        /// The number or URI to transfer to
+        dest: String,
+
+        /// The line to dial the target on
+        #[arg(long="line", short='l', default_value_t=1)]
+        line: u32,
+
+        /// The type of call to place to the target
+        #[arg(long="type", short='t', default_value_t = DialType::Tel, value_parser = clap::builder::EnumValueParser::<DialType>::new())]
+        dial_type: DialType
+    },
+    /// Complete a consultative transfer, connecting the held call to the target
+    Complete {
+        #[arg(value_name="CALL_REF")]
+        call_ref: CallHandle,
+
+        #[arg(value_name="CONSULT_REF")]
+        consult_ref: CallHandle,
+
+        /// The number or URI the consult call was placed to
+        dest: String
+    },
+    /// Cancel a consultative transfer, ending the consult call and resuming the original
+    Cancel {
+        #[arg(value_name="CALL_REF")]
+        call_ref: CallHandle,
+
+        #[arg(value_name="CONSULT_REF")]
+        consult_ref: CallHandle
+    },
+    /// Print the default transfer type
+    GetType,
+    /// Set the default transfer type
+    SetType {
+        #[arg(value_parser = clap::builder::EnumValueParser::<TransferType>::new())]
+        transfer_type: TransferType
+    }
 }
 
 #[derive(Debug, Subcommand)]
@@ -111,25 +335,92 @@ pub enum MgmtCommands{
     Network,
     /// Print network stats
     NetStats,
+    /// Print CPU and memory stats
+    DeviceStats,
+    /// Print RTP stats for every active call
+    MediaStats,
+    /// Print line registration info
+    Lines,
+    /// Print the device's running config, or write it out as a Polycom .cfg file
+    RunningConfig {
+        /// Write the config to the given path as a Polycom XML config file
+        #[arg(long="out", short='o')]
+        out: Option<String>,
+
+        /// Include parameters that are still set to their default values
+        #[arg(long="include-defaults")]
+        include_defaults: bool
+    },
+    /// Print the device's call logs
+    CallLogs {
+        /// The call log to fetch. Fetches all logs if unset
+        #[arg(long="kind", short='k', value_parser = clap::builder::EnumValueParser::<CallLogKind>::new())]
+        kind: Option<CallLogKind>,
+
+        /// The output format
+        #[arg(long="format", short='f', value_enum, default_value_t = OutputFormat::Text)]
+        format: OutputFormat
+    },
     /// Print device config
     Config {
         #[clap(subcommand)]
         subcommand: ConfigSetGetSubcommand
+    },
+    /// Tell the device to re-fetch its configuration from the provisioning server
+    UpdateConfig {
+        /// Wait for the device to report that it is ready again
+        #[arg(long)]
+        wait: bool,
+
+        /// How long to wait, in seconds
+        #[arg(long, default_value_t=300)]
+        timeout: u64
+    },
+    /// Print whether the device is idle, busy or restarting
+    Status,
+    /// Type text into the currently focused field on the phone's screen
+    Type {
+        text: String
+    },
+    /// Restart the phone application once the device is idle
+    Restart {
+        /// Skip the confirmation prompt
+        #[arg(long, short='y')]
+        yes: bool
+    },
+    /// Reboot the device once it is idle
+    Reboot {
+        /// Skip the confirmation prompt
+        #[arg(long, short='y')]
+        yes: bool
+    },
+    /// Reset the device to factory defaults
+    FactoryReset {
+        /// Skip the confirmation prompt
+        #[arg(long, short='y')]
+        yes: bool
     }
 }
 
 #[derive(Debug, Subcommand)]
 pub enum ConfigSetGetSubcommand {
-    /// Get a config value
+    /// Get one or more config values. Keys may use `*` wildcards, e.g. `reg.1.*`
     Get {
-        #[arg(value_name="CONFIG_VAL")]
-        value: String
+        #[arg(value_name="CONFIG_KEY", required=true)]
+        keys: Vec<String>
     },
+    /// Set one or more config values in a single request
     Set {
-        #[arg(value_name="CONFIG_KEY")]
-        key: String,
+        /// Values to set, as `key=value` pairs
+        #[arg(value_name="KEY=VALUE", required_unless_present="from_file")]
+        pairs: Vec<String>,
+
+        /// Read values to set from a JSON object of keys and values
+        #[arg(long="from-file")]
+        from_file: Option<String>,
 
-        #[arg(value_name="CONFIG_VALUE")]
-        value: String
+        /// Allow parameters that aren't in the parameter catalog
+        #[arg(long="allow-unknown")]
+        allow_unknown: bool
     }
 }
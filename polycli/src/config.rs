@@ -0,0 +1,133 @@
+use std::collections::BTreeMap;
+use anyhow::Context;
+use libpoly::{catalog::{self, ParamSpec}, cfg::parse_cfg_xml, errors::PolyRestError, polyrest::{mgmt::ConfigResponseValue, PolyRest}};
+
+use crate::backup::read_backup;
+
+/// A difference in a single parameter between two configs
+#[derive(Debug)]
+pub enum ConfigChange<'a> {
+    /// Only set on the right-hand side
+    Added(&'a ConfigResponseValue),
+    /// Only set on the left-hand side
+    Removed(&'a ConfigResponseValue),
+    /// Set on both sides, to different values
+    Changed(&'a ConfigResponseValue, &'a ConfigResponseValue)
+}
+
+/// Compare two sets of parameters. Parameters that differ only in their source are not reported.
+pub fn diff_configs<'a>(left: &'a BTreeMap<String, ConfigResponseValue>, right: &'a BTreeMap<String, ConfigResponseValue>) -> BTreeMap<&'a str, ConfigChange<'a>> {
+    let mut changes = BTreeMap::new();
+    for (key, lval) in left {
+        match right.get(key) {
+            None => { changes.insert(key.as_str(), ConfigChange::Removed(lval)); },
+            Some(rval) if rval.value != lval.value => { changes.insert(key.as_str(), ConfigChange::Changed(lval, rval)); },
+            Some(_) => {}
+        }
+    }
+    for (key, rval) in right {
+        if !left.contains_key(key) {
+            changes.insert(key.as_str(), ConfigChange::Added(rval));
+        }
+    }
+    changes
+}
+
+/// Load a config from a device URL, a backup archive, or a Polycom .cfg file.
+/// `device` refers to the device given with --url.
+pub async fn load_config(spec: &str, user: &str, pass: &str, url: &str) -> anyhow::Result<BTreeMap<String, ConfigResponseValue>> {
+    let device_url = match spec {
+        "device" => Some(url),
+        spec if spec.starts_with("http://") || spec.starts_with("https://") => Some(spec),
+        _ => None
+    };
+
+    if let Some(device_url) = device_url {
+        let handler = PolyRest::new(user, pass, device_url, true)?;
+        return Ok(handler.running_config().await?)
+    }
+
+    if spec.ends_with(".tar") {
+        return Ok(read_backup(spec)?.running_config)
+    }
+
+    let raw = std::fs::read_to_string(spec).with_context(|| format!("reading {}", spec))?;
+    let params = parse_cfg_xml(&raw)?;
+    // a config file doesn't record where its values came from, so use the file itself as the source
+    Ok(params.into_iter().map(|(key, value)| (key, ConfigResponseValue { value, source: spec.to_string() })).collect())
+}
+
+pub async fn run_diff(left: &str, right: &str, ignore_defaults: bool, user: &str, pass: &str, url: &str) -> anyhow::Result<()> {
+    let mut lconfig = load_config(left, user, pass, url).await?;
+    let mut rconfig = load_config(right, user, pass, url).await?;
+    if ignore_defaults {
+        lconfig.retain(|_, val| val.source != "default");
+        rconfig.retain(|_, val| val.source != "default");
+    }
+
+    let changes = diff_configs(&lconfig, &rconfig);
+    println!("--- {}\n+++ {}", left, right);
+    for (key, change) in &changes {
+        match change {
+            ConfigChange::Added(val) => println!("+ {} = {:?} ({})", key, val.value, val.source),
+            ConfigChange::Removed(val) => println!("- {} = {:?} ({})", key, val.value, val.source),
+            ConfigChange::Changed(lval, rval) => println!("~ {} = {:?} ({}) -> {:?} ({})", key, lval.value, lval.source, rval.value, rval.source),
+        }
+    }
+    println!("{} parameters differ", changes.len());
+    Ok(())
+}
+
+fn print_spec(spec: &ParamSpec) {
+    println!("{}", spec.key);
+    println!("  type:    {}", spec.param_type);
+    println!("  default: {}", spec.default.map(|d| format!("{:?}", d)).unwrap_or_else(|| "none".to_string()));
+    println!("  reboot:  {}", if spec.reboot { "required" } else { "not required" });
+    println!("  {}", spec.description);
+}
+
+pub fn run_describe(key: &str) -> anyhow::Result<()> {
+    match catalog::lookup(key) {
+        Some(spec) => print_spec(spec),
+        None => return Err(PolyRestError::UnknownParameter { key: key.to_string(), suggestion: catalog::suggest(key) }.into())
+    }
+    Ok(())
+}
+
+pub fn run_search(term: &str) {
+    let found = catalog::search(term);
+    for spec in &found {
+        print_spec(spec);
+    }
+    println!("{} parameters found", found.len());
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn val(value: &str, source: &str) -> ConfigResponseValue {
+        ConfigResponseValue { value: value.to_string(), source: source.to_string() }
+    }
+
+    #[test]
+    fn diff() {
+        let left = BTreeMap::from([
+            ("reg.1.address".to_string(), val("1001", "configFile")),
+            ("reg.1.label".to_string(), val("Desk", "web")),
+            ("up.backlight.idleIntensity".to_string(), val("1", "default")),
+        ]);
+        let right = BTreeMap::from([
+            ("reg.1.address".to_string(), val("1002", "configFile")),
+            ("reg.1.label".to_string(), val("Desk", "configFile")),
+            ("voIpProt.server.1.address".to_string(), val("pbx", "configFile")),
+        ]);
+
+        let changes = diff_configs(&left, &right);
+        assert_eq!(changes.len(), 3);
+        assert!(matches!(changes["reg.1.address"], ConfigChange::Changed(_, _)));
+        assert!(matches!(changes["up.backlight.idleIntensity"], ConfigChange::Removed(_)));
+        assert!(matches!(changes["voIpProt.server.1.address"], ConfigChange::Added(_)));
+    }
+}
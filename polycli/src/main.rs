@@ -1,83 +1,325 @@
+use std::{io::{self, BufRead, Write}, time::{Duration, Instant}};
 use anyhow::Result;
+use indexmap::IndexMap;
 use clap::Parser;
-use cli::{Cli, Commands, ConfigSetGetSubcommand, PushSubcommands, RestCommands};
-use libpoly::{polyrest::PolyRest, push::{self, MessageLevel}};
+use cli::{Cli, Commands, ConfigCommands, ConfigSetGetSubcommand, CtrlCommands, PushSubcommands, RestCommands, TransferCommands};
+use libpoly::{cfg::to_cfg_xml, errors::PolyRestError, polyrest::{callctrl::ConsultativeTransfer, mgmt::{uptime_seconds, DeviceState, READY_POLL_INTERVAL}, PolyRest}, push::{self, MessageLevel}};
+use backup::{run_backup, run_restore};
+use config::{run_describe, run_diff, run_search};
+use state::{run_apply, run_plan};
+use output::format_call_logs;
 use provision::run_provision;
+use simulate::run_simulate;
 use tmpl::render_alert_template;
 
 mod cli;
 mod tmpl;
 mod provision;
+mod output;
+mod backup;
+mod config;
+mod state;
+mod simulate;
 
-fn run_cfg_getset(handler: &mut PolyRest, subcommand: ConfigSetGetSubcommand) -> Result<()> {
+/// How long to watch for the device to restart after a config update, before assuming it applied the update without restarting
+const UPDATE_RESTART_WINDOW: Duration = Duration::from_secs(30);
+
+async fn run_update_config(handler: &PolyRest, wait: bool, timeout: u64) -> Result<()> {
+    let before = uptime_seconds(&handler.device_info().await?.up_time_since_last_reboot);
+    let resp = handler.update_configuration().await?;
+    println!("{:#?}", resp);
+    if !wait {
+        return Ok(())
+    }
+
+    let timeout = Duration::from_secs(timeout);
+    let restarted = wait_for_restart(handler, before, UPDATE_RESTART_WINDOW.min(timeout)).await?;
+    handler.wait_until_ready(timeout).await?;
+    if restarted {
+        println!("device restarted and is ready");
+    } else {
+        println!("device is ready");
+    }
+    Ok(())
+}
+
+/// Poll the device until it shows it is restarting: it stops answering, reports that it's restarting, or its uptime goes back.
+/// Returns false if none of that happens within the window.
+async fn wait_for_restart(handler: &PolyRest, uptime_before: Option<u64>, window: Duration) -> Result<bool> {
+    let start = Instant::now();
+    while start.elapsed() < window {
+        match handler.poll_for_status().await {
+            Ok(status) if status.state == DeviceState::Restarting => return Ok(true),
+            Ok(_) | Err(PolyRestError::UnsupportedEndpoint(_)) => {},
+            Err(err) if err.is_transient() => return Ok(true),
+            Err(err) => return Err(err.into())
+        }
+        match handler.device_info().await {
+            Ok(info) => {
+                let uptime = uptime_seconds(&info.up_time_since_last_reboot);
+                if uptime.zip(uptime_before).is_some_and(|(now, before)| now < before) {
+                    return Ok(true)
+                }
+            },
+            Err(err) if err.is_transient() => return Ok(true),
+            Err(err) => return Err(err.into())
+        }
+        tokio::time::sleep(READY_POLL_INTERVAL).await;
+    }
+    Ok(false)
+}
+
+/// Ask the user to confirm a destructive action. Returns true if the user answered yes.
+fn confirm(prompt: &str) -> Result<bool> {
+    print!("{} [y/N] ", prompt);
+    io::stdout().flush()?;
+
+    let mut answer = String::new();
+    io::stdin().lock().read_line(&mut answer)?;
+    Ok(matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"))
+}
+
+async fn run_cfg_getset(handler: &PolyRest, subcommand: ConfigSetGetSubcommand) -> Result<()> {
     match subcommand {
-        ConfigSetGetSubcommand::Get { value } => {
-            let values = handler.config_get(value)?;
+        ConfigSetGetSubcommand::Get { keys } => {
+            let values = handler.config_get(keys).await?;
             println!("{:#?}", values);
         },
-        ConfigSetGetSubcommand::Set { key, value } => {
-            let res = handler.config_set(key, value)?;
-            println!("{}", res);
+        ConfigSetGetSubcommand::Set { pairs, from_file, allow_unknown } => {
+            let handler = handler.clone().with_allow_unknown_params(allow_unknown);
+            let mut changes: IndexMap<String, String> = IndexMap::new();
+            if let Some(path) = from_file {
+                let raw: IndexMap<String, serde_json::Value> = serde_json::from_str(&std::fs::read_to_string(path)?)?;
+                for (key, value) in raw {
+                    let value = match value {
+                        serde_json::Value::String(s) => s,
+                        serde_json::Value::Number(_) | serde_json::Value::Bool(_) => value.to_string(),
+                        other => anyhow::bail!("value for {} must be a string, number or bool, got {}", key, other)
+                    };
+                    changes.insert(key, value);
+                }
+            }
+            for pair in pairs {
+                let Some((key, value)) = pair.split_once('=') else {
+                    anyhow::bail!("expected KEY=VALUE, got '{}'", pair)
+                };
+                changes.insert(key.to_string(), value.to_string());
+            }
+
+            let results = handler.config_set(changes).await?;
+            for (key, status) in &results {
+                println!("{}: {}", key, status);
+            }
+            let failed = results.values().filter(|status| !status.is_success()).count();
+            if failed > 0 {
+                anyhow::bail!("{} of {} values were not set", failed, results.len())
+            }
+        }
+    };
+
+    Ok(())
+}
+
+async fn run_transfer_cmd(handler: &PolyRest, subcommand: TransferCommands) -> Result<()> {
+    match subcommand {
+        TransferCommands::Blind { call_ref, dest } => {
+            let resp = handler.blind_transfer(call_ref, dest).await?;
+            println!("{:#?}", resp);
+        },
+        TransferCommands::Start { call_ref, dest, line, dial_type } => {
+            let transfer = handler.start_consultative_transfer(call_ref, dest, line, dial_type).await?;
+            println!("{:#?}", transfer);
+        },
+        TransferCommands::Complete { call_ref, consult_ref, dest } => {
+            let transfer = ConsultativeTransfer { original: call_ref, consult: consult_ref, target: dest };
+            let resp = handler.complete_transfer(transfer).await?;
+            println!("{:#?}", resp);
+        },
+        TransferCommands::Cancel { call_ref, consult_ref } => {
+            let transfer = ConsultativeTransfer { original: call_ref, consult: consult_ref, target: String::new() };
+            let resp = handler.cancel_transfer(transfer).await?;
+            println!("{:#?}", resp);
+        },
+        TransferCommands::GetType => {
+            let transfer_type = handler.transfer_type().await?;
+            println!("{}", transfer_type);
+        },
+        TransferCommands::SetType { transfer_type } => {
+            let resp = handler.set_transfer_type(transfer_type).await?;
+            println!("{:#?}", resp);
         }
     };
 
     Ok(())
 }
 
-fn run_msg_cmd(username: String, password: String, url: String, subcommand: PushSubcommands, level: MessageLevel) -> anyhow::Result<()> {
-    let mut handler = push::PushMessenger::new(username, password, url, true)?;
+async fn run_ctrl_cmd(handler: &PolyRest, subcommand: CtrlCommands) -> Result<()> {
+    let resp = match subcommand {
+        CtrlCommands::Status => {
+            let status = handler.call_status().await?;
+            println!("{:#?}", status);
+            return Ok(())
+        },
+        CtrlCommands::Transfer { subcommand } => {
+            return run_transfer_cmd(handler, subcommand).await
+        },
+        CtrlCommands::Dtmf { call_ref, digits } => {
+            for ack in handler.send_dtmf(call_ref, &digits).await? {
+                println!("{:#?}", ack);
+            }
+            return Ok(())
+        },
+        CtrlCommands::Dial { number, line, dial_type } => handler.dial(number, line, dial_type).await?,
+        CtrlCommands::Answer { call_ref } => handler.answer_call(call_ref).await?,
+        CtrlCommands::Ignore { call_ref } => handler.ignore_call(call_ref).await?,
+        CtrlCommands::Reject { call_ref } => handler.reject_call(call_ref).await?,
+        CtrlCommands::End { call_ref } => handler.end_call(call_ref).await?,
+        CtrlCommands::Hold { call_ref } => handler.hold_call(call_ref).await?,
+        CtrlCommands::Resume { call_ref } => handler.resume_call(call_ref).await?,
+    };
+
+    println!("{:#?}", resp);
+    Ok(())
+}
+
+async fn run_msg_cmd(username: String, password: String, url: String, subcommand: PushSubcommands, level: MessageLevel) -> anyhow::Result<()> {
+    let handler = push::PushMessenger::new(username, password, url, true)?;
 
 
     let resp = match subcommand {
         PushSubcommands::Alert { header, str_msg } => {
             let rendered = render_alert_template(header, str_msg)?;
-            handler.send(level, rendered, push::PushType::HTML)?
+            handler.send(level, rendered, push::PushType::HTML).await?
         },
         PushSubcommands::Html { msg } => {
-            handler.send(level, msg, push::PushType::HTML)?
+            handler.send(level, msg, push::PushType::HTML).await?
         },
         PushSubcommands::Cmd { subcommand } => {
             match subcommand {
-                cli::PushCmdSubcommands::Dial { number } => {
-                    let dial_cmd = format!("tel:\\{}", number);
-                    handler.send(level, dial_cmd, push::PushType::Command)?
+                cli::PushCmdSubcommands::Dial { number, line } => {
+                    handler.send_commands(level, &[push::PushCommand::Dial { number, line }]).await?
+                },
+                cli::PushCmdSubcommands::Fetch { url } => {
+                    handler.send_commands(level, &[push::PushCommand::Fetch(url)]).await?
+                },
+                cli::PushCmdSubcommands::Key { keys } => {
+                    let key_cmds: Vec<push::PushCommand> = keys.into_iter().map(push::PushCommand::Key).collect();
+                    handler.send_commands(level, &key_cmds).await?
+                },
+                cli::PushCmdSubcommands::SoftKey { action } => {
+                    handler.send_commands(level, &[push::PushCommand::SoftKey(action)]).await?
                 },
-                cli::PushCmdSubcommands::Key { key } => {
-                    let key_cmd = format!("Key:{}", key);
-                    handler.send(level, key_cmd, push::PushType::Command)?
+                cli::PushCmdSubcommands::Uri { uris } => {
+                    handler.send_commands(level, &uris).await?
                 },
                 cli::PushCmdSubcommands::Raw { msg } => {
-                    handler.send(level, msg, push::PushType::Command)?
+                    handler.send(level, msg, push::PushType::Command).await?
                 }
             }
         }
     };
 
+    if !resp.is_accepted() {
+        anyhow::bail!("push was not accepted: {}", resp)
+    }
     println!("{}", resp);
     Ok(())
 }
 
-fn run_rest_cmd(username: String, password: String, url: String, cmd: RestCommands) -> anyhow::Result<()> {
-    let mut handler = PolyRest::new(username, password, url, true)?; // TODO: set secure bool from CLI
+async fn run_rest_cmd(username: String, password: String, url: String, cmd: RestCommands) -> anyhow::Result<()> {
+    let handler = PolyRest::new(username, password, url, true)?; // TODO: set secure bool from CLI
 
     match cmd {
-        RestCommands::Ctrl => {},
+        RestCommands::Ctrl { subcommand } => {
+            run_ctrl_cmd(&handler, subcommand).await?;
+        },
         RestCommands::Mgmt { subcommand } => {
             match subcommand {
                 cli::MgmtCommands::Info => {
-                    let info = handler.device_info()?;
+                    let info = handler.device_info().await?;
                     println!("{:#?}", info)
                 },
                 cli::MgmtCommands::Network => {
-                    let info = handler.network_info()?;
+                    let info = handler.network_info().await?;
                     println!("{:#?}", info)
                 }, 
                 cli::MgmtCommands::NetStats => {
-                    let stats = handler.network_stats()?;
+                    let stats = handler.network_stats().await?;
                     println!("{:#?}", stats);
                 }, 
+                cli::MgmtCommands::DeviceStats => {
+                    let stats = handler.device_stats().await?;
+                    println!("{:#?}", stats);
+                },
+                cli::MgmtCommands::MediaStats => {
+                    let stats = handler.media_session_stats().await?;
+                    println!("{:#?}", stats);
+                },
+                cli::MgmtCommands::RunningConfig { out, include_defaults } => {
+                    let config = handler.running_config().await?;
+                    match out {
+                        Some(path) => {
+                            let params = config.into_iter()
+                                .filter(|(_, val)| include_defaults || val.source != "default")
+                                .map(|(key, val)| (key, val.value))
+                                .collect();
+                            std::fs::write(&path, to_cfg_xml(&params))?;
+                            println!("wrote {} parameters to {}", params.len(), path);
+                        },
+                        None => println!("{:#?}", config)
+                    }
+                },
+                cli::MgmtCommands::Lines => {
+                    let lines = handler.line_info().await?;
+                    println!("{:#?}", lines);
+                },
+                cli::MgmtCommands::CallLogs { kind, format } => {
+                    let logs = match kind {
+                        Some(kind) => handler.call_logs(kind).await?,
+                        None => handler.all_call_logs().await?
+                    };
+                    println!("{}", format_call_logs(&logs, format)?);
+                },
                 cli::MgmtCommands::Config { subcommand } => {
-                    run_cfg_getset(&mut handler, subcommand)?;
+                    run_cfg_getset(&handler, subcommand).await?;
+                },
+                cli::MgmtCommands::UpdateConfig { wait, timeout } => {
+                    run_update_config(&handler, wait, timeout).await?;
+                },
+                cli::MgmtCommands::Status => {
+                    let status = handler.poll_for_status().await?;
+                    println!("{:#?}", status);
+                },
+                cli::MgmtCommands::Type { text } => {
+                    let resp = handler.simulate_text_input(text).await?;
+                    println!("{:#?}", resp);
+                },
+                cli::MgmtCommands::Restart { yes } => {
+                    if !yes && !confirm("Restart the phone application?")? {
+                        anyhow::bail!("aborted")
+                    }
+                    let resp = handler.safe_restart().await?;
+                    println!("{:#?}", resp);
+                },
+                cli::MgmtCommands::Reboot { yes } => {
+                    if !yes && !confirm("Reboot the device?")? {
+                        anyhow::bail!("aborted")
+                    }
+                    let resp = handler.safe_reboot().await?;
+                    println!("{:#?}", resp);
+                },
+                cli::MgmtCommands::FactoryReset { yes } => {
+                    let confirmed = yes || {
+                        let info = handler.device_info().await?;
+                        let prompt = format!("Factory reset {} {} (MAC {})? All local configuration will be lost.",
+                            info.device_vendor, info.model_number, info.mac_address);
+                        confirm(&prompt)?
+                    };
+                    if !confirmed {
+                        anyhow::bail!("aborted")
+                    }
+                    let resp = handler.factory_reset().await?;
+                    println!("{:#?}", resp);
                 },
             }
         }
@@ -92,13 +334,33 @@ async fn main() -> Result<()> {
 
     match args.command {
         Commands::Rest{subcommand} => {
-            run_rest_cmd(args.user, args.pass, args.url, subcommand)?; 
+            run_rest_cmd(args.user, args.pass, args.url, subcommand).await?; 
         }, 
         Commands::Push { subcommand, level } => {
-            run_msg_cmd(args.user, args.pass, args.url, subcommand, level)?;
+            run_msg_cmd(args.user, args.pass, args.url, subcommand, level).await?;
+        },
+        Commands::Config { subcommand } => {
+            match subcommand {
+                ConfigCommands::Diff { left, right, ignore_defaults } => run_diff(&left, &right, ignore_defaults, &args.user, &args.pass, &args.url).await?,
+                ConfigCommands::Plan { path, allow_unknown } => run_plan(&path, allow_unknown, &args.user, &args.pass, &args.url).await?,
+                ConfigCommands::Apply { path, yes, allow_unknown } => run_apply(&path, yes, allow_unknown, &args.user, &args.pass, &args.url).await?,
+                ConfigCommands::Describe { key } => run_describe(&key)?,
+                ConfigCommands::Search { term } => run_search(&term)
+            }
+        },
+        Commands::Backup { out, provision_root } => {
+            let handler = PolyRest::new(args.user, args.pass, args.url, true)?;
+            run_backup(&handler, out, provision_root).await?;
+        },
+        Commands::Restore { path, yes } => {
+            let handler = PolyRest::new(args.user, args.pass, args.url, true)?;
+            run_restore(handler, path, yes).await?;
         },
         Commands::Provisioner { port, path } => {
             run_provision(format!("0.0.0.0:{}", port), path).await?;
+        },
+        Commands::Simulate { push_user, push_pass } => {
+            run_simulate(&args.url, args.user, args.pass, push_user, push_pass).await?;
         }
     };
 
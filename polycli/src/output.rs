@@ -0,0 +1,60 @@
+use clap::ValueEnum;
+use libpoly::polyrest::calllog::CallLogEntry;
+
+
+/// The format used to print tabular results
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum OutputFormat {
+    /// Rust debug output
+    Text,
+    /// Comma-separated values, with a header row
+    Csv,
+    /// A JSON array
+    Json
+}
+
+/// Escape and join a single CSV row
+fn csv_row<S: AsRef<str>>(fields: &[S]) -> String {
+    fields.iter().map(|field| {
+        let field = field.as_ref();
+        if field.contains([',', '"', '\n', '\r']) {
+            format!("\"{}\"", field.replace('"', "\"\""))
+        } else {
+            field.to_string()
+        }
+    }).collect::<Vec<String>>().join(",")
+}
+
+pub fn format_call_logs(entries: &[CallLogEntry], format: OutputFormat) -> anyhow::Result<String> {
+    let out = match format {
+        OutputFormat::Text => format!("{:#?}", entries),
+        OutputFormat::Json => serde_json::to_string_pretty(entries)?,
+        OutputFormat::Csv => {
+            let mut rows = vec![csv_row(&["direction", "disposition", "remote_party_name", "remote_party_number", "line", "start_time", "duration"])];
+            for entry in entries {
+                rows.push(csv_row(&[
+                    entry.direction.to_string(),
+                    entry.disposition.to_string(),
+                    entry.remote_party_name.clone(),
+                    entry.remote_party_number.clone(),
+                    entry.line.clone(),
+                    entry.start_time.clone(),
+                    entry.duration.to_string(),
+                ]));
+            }
+            rows.join("\n")
+        }
+    };
+    Ok(out)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn csv_escaping() {
+        assert_eq!(csv_row(&["a", "b,c", "say \"hi\""]), "a,\"b,c\",\"say \"\"hi\"\"\"");
+    }
+}
@@ -7,7 +7,7 @@ use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 use tracing::{info_span, Span};
 
 
-pub async fn run_provision(endpoint: String, filepath: String) -> anyhow::Result<()> {
+pub fn init_tracing() {
     tracing_subscriber::registry()
     .with(
         tracing_subscriber::EnvFilter::try_from_default_env()
@@ -15,7 +15,26 @@ pub async fn run_provision(endpoint: String, filepath: String) -> anyhow::Result
     )
     .with(tracing_subscriber::fmt::layer())
     .init();
+}
+
+pub async fn run_provision(endpoint: String, filepath: String) -> anyhow::Result<()> {
+    init_tracing();
+
+    let route = provision_router(filepath);
+
+    // actually do server things
+    let listener = tokio::net::TcpListener::bind(endpoint)
+    .await?;
+
+    tracing::debug!("listening on {}", listener.local_addr()?);
+    axum::serve(listener, route).await?;
+
+    Ok(())
 
+}
+
+/// Serve the files under `filepath` to phones, and accept the logs they upload
+fn provision_router(filepath: String) -> Router {
     let put_handle = PutFallback::new(filepath.clone());
     let serve_dir = ServeDir::new(filepath)
     .fallback(put_handle)
@@ -48,15 +67,7 @@ pub async fn run_provision(endpoint: String, filepath: String) -> anyhow::Result
             })
         );
 
-    // actually do server things
-    let listener = tokio::net::TcpListener::bind(endpoint)
-    .await?;
-
-    tracing::debug!("listening on {}", listener.local_addr()?);
-    axum::serve(listener, route).await?;
-
-    Ok(())
-
+    route
 }
 
 
@@ -145,9 +156,32 @@ impl Service<Request<Body>> for PutFallback {
 mod tests {
     use super::*;
 
+    async fn request(router: &mut Router, method: Method, path: &str, body: &str) -> (StatusCode, String) {
+        std::future::poll_fn(|cx| Service::<Request<Body>>::poll_ready(router, cx)).await.unwrap();
+        let req = Request::builder().method(method).uri(path).body(Body::from(body.to_string())).unwrap();
+        let resp = router.call(req).await.unwrap();
+        let status = resp.status();
+        let body = axum::body::to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+        (status, String::from_utf8_lossy(&body).to_string())
+    }
+
     #[tokio::test]
     async fn  test_server() {
-        run_provision("0.0.0.0:8000".to_string(), "/home/alexk/Documents/polycom/firmware/UC_Software_6_4_6_release_sig_split".to_string()).await.unwrap();
+        let root = std::env::temp_dir().join(format!("polycli-provision-test-{}", std::process::id()));
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::write(root.join("000000000000.cfg"), "<polycomConfig/>").unwrap();
+        let mut router = provision_router(root.to_string_lossy().to_string());
+
+        assert_eq!(request(&mut router, Method::GET, "/000000000000.cfg", "").await, (StatusCode::OK, "<polycomConfig/>".to_string()));
+        assert_eq!(request(&mut router, Method::GET, "/missing.cfg", "").await.0, StatusCode::NOT_FOUND);
+
+        // phones append to their logs with PUT
+        request(&mut router, Method::PUT, "/64167fcacee2-app.log", "first\n").await;
+        let (status, _) = request(&mut router, Method::PUT, "/64167fcacee2-app.log", "second\n").await;
+        assert_eq!(status, StatusCode::OK);
+        let log = std::fs::read_to_string(root.join("64167fcacee2-app.log")).unwrap();
+        std::fs::remove_dir_all(&root).unwrap();
+        assert_eq!(log, "first\nsecond\n");
     }
 
 }
\ No newline at end of file
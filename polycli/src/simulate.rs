@@ -0,0 +1,535 @@
+use std::{collections::{BTreeMap, HashMap}, sync::{Arc, Mutex, MutexGuard}, time::{Duration, Instant, SystemTime, UNIX_EPOCH}};
+use axum::{extract::{Path, Request, State}, http::{header, HeaderMap, StatusCode, Uri}, middleware::{self, Next},
+    response::{IntoResponse, Response}, routing::{get, post}, Json, Router};
+use anyhow::{bail, Context};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use digest_auth::{AuthContext, AuthorizationHeader, HttpMethod};
+use libpoly::{catalog::CATALOG, polyrest::{mgmt::{ConfigResponseValue, TransferType}, STATUS_SUCCESS}};
+use serde::Serialize;
+use serde_json::{json, Value};
+
+use crate::provision::init_tracing;
+
+const MODEL_NUMBER: &str = "VVX 411";
+const FIRMWARE_RELEASE: &str = "5.5.0.22173";
+const MAC_ADDRESS: &str = "0004f2000001";
+const DIGEST_REALM: &str = "PPHONE";
+
+/// How long the simulated device reports itself as restarting after a restart, reboot or config update
+const RESTART_TIME: Duration = Duration::from_secs(5);
+
+/// How long a push digest nonce is accepted before the device asks for a new one
+const NONCE_LIFETIME: Duration = Duration::from_secs(300);
+
+/// A push request accepted by the simulator
+#[derive(Debug, Clone, Serialize)]
+pub struct ReceivedPush {
+    pub content_type: String,
+    pub body: String,
+}
+
+struct Device {
+    config: BTreeMap<String, ConfigResponseValue>,
+    transfer_type: TransferType,
+    booted: Instant,
+    busy_until: Option<Instant>,
+    /// digest nonces, and when they were issued
+    nonces: HashMap<String, Instant>,
+    pushes: Vec<ReceivedPush>,
+    calls: Vec<Call>,
+    next_call: u32,
+}
+
+/// A call on the simulated device. Dialed calls connect straight away.
+struct Call {
+    handle: String,
+    line: String,
+    remote: String,
+    held: bool,
+    started: Instant,
+}
+
+impl Device {
+    fn new(push_user: &str, push_pass: &str) -> Self {
+        let mut config = default_config();
+        for (key, value) in [
+            ("reg.1.address", "1001"),
+            ("reg.1.label", "1001"),
+            ("reg.1.server.1.address", "pbx.example.com"),
+            ("apps.push.messageType", "5"),
+            ("apps.push.username", push_user),
+            ("apps.push.password", push_pass),
+        ] {
+            config.insert(key.to_string(), ConfigResponseValue { value: value.to_string(), source: "configFile".to_string() });
+        }
+        Self { config, transfer_type: TransferType::Consultative, booted: Instant::now(), busy_until: None, nonces: HashMap::new(), pushes: Vec::new(),
+            calls: Vec::new(), next_call: 0x4b8a6e8 }
+    }
+
+    fn value(&self, key: &str) -> &str {
+        self.config.get(key).map(|val| val.value.as_str()).unwrap_or_default()
+    }
+
+    /// Format the uptime like the phone does, e.g. "0 Day 6:16:10"
+    fn uptime(&self) -> String {
+        let secs = self.booted.elapsed().as_secs();
+        format!("{} Day {}:{:02}:{:02}", secs / 86400, secs / 3600 % 24, secs / 60 % 60, secs % 60)
+    }
+
+    fn call_index(&self, handle: &str) -> Option<usize> {
+        self.calls.iter().position(|call| call.handle == handle)
+    }
+
+    fn restart(&mut self, reboot: bool) {
+        let ready = Instant::now() + RESTART_TIME;
+        self.busy_until = Some(ready);
+        if reboot {
+            self.booted = ready;
+        }
+    }
+}
+
+/// The catalog defaults, with the first line and server filled in for indexed parameters
+fn default_config() -> BTreeMap<String, ConfigResponseValue> {
+    CATALOG.iter()
+        .filter_map(|spec| spec.default.map(|default| (spec.key, default)))
+        .map(|(key, default)| {
+            let key = key.split('.').map(|part| if part == "x" || part == "y" { "1" } else { part }).collect::<Vec<_>>().join(".");
+            (key, ConfigResponseValue { value: default.to_string(), source: "default".to_string() })
+        })
+        .collect()
+}
+
+/// Emulates the REST and push APIs of a VVX phone
+#[derive(Clone)]
+pub struct Simulator {
+    device: Arc<Mutex<Device>>,
+    username: String,
+    password: String,
+}
+
+impl Simulator {
+    /// Create a simulated device. `username` and `password` are the REST API credentials;
+    /// the push credentials are stored in the `apps.push.*` parameters, and can be changed with config set.
+    pub fn new(username: &str, password: &str, push_user: &str, push_pass: &str) -> Self {
+        Self { device: Arc::new(Mutex::new(Device::new(push_user, push_pass))), username: username.to_string(), password: password.to_string() }
+    }
+
+    fn device(&self) -> MutexGuard<'_, Device> {
+        self.device.lock().unwrap()
+    }
+
+    /// Every push request the device has accepted, in order
+    pub fn pushes(&self) -> Vec<ReceivedPush> {
+        self.device().pushes.clone()
+    }
+
+    pub fn router(&self) -> Router {
+        let mgmt = Router::new()
+            .route("/mgmt/device/info", get(device_info))
+            .route("/mgmt/network/info", get(network_info))
+            .route("/mgmt/network/stats", get(network_stats))
+            .route("/mgmt/device/stats", get(device_stats))
+            .route("/mgmt/media/sessionStats", get(|| async { success(json!([])) }))
+            .route("/mgmt/device/runningConfig", get(running_config))
+            .route("/mgmt/lineInfo", get(line_info))
+            .route("/mgmt/pollForStatus", get(poll_for_status))
+            .route("/mgmt/callLogs/:kind", get(call_logs))
+            .route("/mgmt/config/get", post(config_get))
+            .route("/mgmt/config/set", post(config_set))
+            .route("/mgmt/transferType/get", get(transfer_type))
+            .route("/mgmt/transferType/set", post(set_transfer_type))
+            .route("/mgmt/simulateTextInput", post(simulate_text_input))
+            .route("/mgmt/:action", post(mgmt_action))
+            .route("/callctrl/:action", post(call_ctrl))
+            .route("/webCallControl/callStatus", get(call_status));
+
+        Router::new()
+            .nest("/api/v1", mgmt.route_layer(middleware::from_fn_with_state(self.clone(), basic_auth)))
+            .route("/push", post(push))
+            // not part of the phone's API; lets scripts check what was pushed
+            .route("/sim/pushes", get(|State(sim): State<Simulator>| async move { Json(sim.pushes()) }))
+            .with_state(self.clone())
+    }
+}
+
+fn success(data: Value) -> Json<Value> {
+    Json(json!({"data": data, "Status": STATUS_SUCCESS}))
+}
+
+fn acknowledge() -> Json<Value> {
+    Json(json!({"Status": STATUS_SUCCESS}))
+}
+
+fn invalid_request() -> Response {
+    (StatusCode::BAD_REQUEST, Json(json!({"Status": "4000"}))).into_response()
+}
+
+/// Reject REST requests that don't carry the configured credentials, as the phone does
+async fn basic_auth(State(sim): State<Simulator>, req: Request, next: Next) -> Response {
+    let expected = format!("Basic {}", STANDARD.encode(format!("{}:{}", sim.username, sim.password)));
+    match req.headers().get(header::AUTHORIZATION) {
+        Some(auth) if auth.as_bytes() == expected.as_bytes() => next.run(req).await,
+        _ => StatusCode::UNAUTHORIZED.into_response()
+    }
+}
+
+async fn device_info(State(sim): State<Simulator>) -> Json<Value> {
+    let device = sim.device();
+    success(json!({"ModelNumber": MODEL_NUMBER, "FirmwareRelease": FIRMWARE_RELEASE, "DeviceType": "hardwareEndpoint", "MACAddress": MAC_ADDRESS,
+        "DeviceVendor": "Polycom", "UpTimeSinceLastReboot": device.uptime(), "IPV4Address": "127.0.0.1", "IPV6Address": "::", "AttachedHardware": {"EM": []}}))
+}
+
+async fn network_info(State(sim): State<Simulator>) -> Json<Value> {
+    let device = sim.device();
+    success(json!({"DefaultGateway": "127.0.0.1", "IPV4Address": "127.0.0.1", "IPV6Address": "::", "DHCPServer": "127.0.0.1", "DHCP": "enabled",
+        "UpgradeServer": "", "DHCPOption60Format": "RFC3925", "DHCPBootServerUseOption": "Default", "ZTPStatus": "disabled",
+        "DHCPBootServerOption": "160", "DHCPBootServerOptionType": "String", "LLDP": "enabled", "LANPortStatus": "active", "SubnetMask": "255.0.0.0",
+        "AlternateDNSServer": "", "DNSServer": "127.0.0.1", "DNSDomain": "", "LANSpeed": "1000MB", "SNTPAddress": "pool.ntp.org",
+        "VLANDiscoveryMode": "Fixed", "CDPCompability": "enabled", "VLANID": "", "VLANIDOption": "129",
+        "ProvServerAddress": device.value("device.prov.serverName"), "ProvServerUser": device.value("device.prov.user"),
+        "ProvServerType": device.value("device.prov.serverType")}))
+}
+
+async fn network_stats(State(sim): State<Simulator>) -> Json<Value> {
+    let uptime = sim.device().uptime();
+    success(json!({"UpTime": uptime, "RxPackets": "48213", "TxPackets": "31877"}))
+}
+
+async fn device_stats() -> Json<Value> {
+    success(json!({"CPU": {"Current": "12.4", "Average": "9.8"}, "Memory": {"Total": "249132", "Used": "187404", "Free": "61728"}}))
+}
+
+async fn running_config(State(sim): State<Simulator>) -> Json<Value> {
+    success(json!(sim.device().config))
+}
+
+async fn line_info(State(sim): State<Simulator>) -> Json<Value> {
+    let device = sim.device();
+    let address = device.value("reg.1.address");
+    let server = device.value("reg.1.server.1.address");
+    let status = if address.is_empty() || server.is_empty() { "unregistered" } else { "registered" };
+    success(json!([{"LineNumber": "1", "SIPAddress": format!("{}@{}", address, server), "Label": device.value("reg.1.label"),
+        "RegistrationStatus": status, "ProxyAddress": server, "Port": device.value("reg.1.server.1.port"),
+        "Transport": device.value("reg.1.server.1.transport"), "LineType": "private", "UserID": address, "Protocol": "SIP"}]))
+}
+
+async fn poll_for_status(State(sim): State<Simulator>) -> Json<Value> {
+    let mut device = sim.device();
+    let state = match device.busy_until {
+        Some(until) if until > Instant::now() => "Restarting",
+        _ => {
+            device.busy_until = None;
+            "Idle"
+        }
+    };
+    success(json!({"State": state}))
+}
+
+async fn call_logs(Path(kind): Path<String>) -> Response {
+    let (name, number) = match kind.as_str() {
+        "missed" => ("Front Desk", "1002"),
+        "received" => ("Lobby", "1003"),
+        "placed" => ("Conference Room", "1004"),
+        _ => return StatusCode::NOT_FOUND.into_response()
+    };
+    success(json!([{"LineNumber": "1", "StartTime": "2024-08-20T09:41:12", "Duration": "PT1M32S", "RemotePartyName": name,
+        "RemotePartyNumber": number, "LocalPartyName": "1001", "LocalPartyNumber": "1001", "Count": "1"}])).into_response()
+}
+
+async fn config_get(State(sim): State<Simulator>, body: String) -> Response {
+    let Ok(req) = serde_json::from_str::<Value>(&body) else { return invalid_request() };
+    let Some(keys) = req["data"].as_array() else { return invalid_request() };
+    let device = sim.device();
+    let values: BTreeMap<&str, &ConfigResponseValue> = keys.iter()
+        .filter_map(Value::as_str)
+        .filter_map(|key| device.config.get_key_value(key).map(|(key, val)| (key.as_str(), val)))
+        .collect();
+    success(json!(values)).into_response()
+}
+
+async fn config_set(State(sim): State<Simulator>, body: String) -> Response {
+    let Ok(req) = serde_json::from_str::<Value>(&body) else { return invalid_request() };
+    let Some(changes) = req["data"].as_object() else { return invalid_request() };
+    let mut device = sim.device();
+    for (key, value) in changes {
+        let Some(value) = value.as_str() else { return invalid_request() };
+        tracing::info!(key = key, value = value, "config set");
+        device.config.insert(key.clone(), ConfigResponseValue { value: value.to_string(), source: "web".to_string() });
+    }
+    acknowledge().into_response()
+}
+
+async fn transfer_type(State(sim): State<Simulator>) -> Json<Value> {
+    success(json!({"Type": sim.device().transfer_type}))
+}
+
+async fn set_transfer_type(State(sim): State<Simulator>, body: String) -> Response {
+    let Ok(req) = serde_json::from_str::<Value>(&body) else { return invalid_request() };
+    let Ok(transfer_type) = serde_json::from_value(req["data"]["Type"].clone()) else { return invalid_request() };
+    sim.device().transfer_type = transfer_type;
+    acknowledge().into_response()
+}
+
+async fn simulate_text_input(body: String) -> Response {
+    let Ok(req) = serde_json::from_str::<Value>(&body) else { return invalid_request() };
+    tracing::info!(text = req["data"]["Text"].as_str().unwrap_or_default(), "text input");
+    acknowledge().into_response()
+}
+
+async fn mgmt_action(State(sim): State<Simulator>, Path(action): Path<String>) -> Response {
+    let mut device = sim.device();
+    match action.as_str() {
+        "safeRestart" | "safeReboot" => device.restart(true),
+        "updateConfiguration" => device.restart(false),
+        "factoryReset" => {
+            device.config = default_config();
+            device.restart(true);
+        },
+        _ => return (StatusCode::NOT_FOUND, Json(json!({"Status": "4004"}))).into_response()
+    }
+    tracing::info!(action = action, "mgmt action");
+    acknowledge().into_response()
+}
+
+async fn call_ctrl(State(sim): State<Simulator>, Path(action): Path<String>, body: String) -> Response {
+    let Ok(req) = serde_json::from_str::<Value>(&body) else { return invalid_request() };
+    let data = &req["data"];
+    tracing::info!(action = action, body = body, "call control");
+    let mut device = sim.device();
+
+    if action == "dial" {
+        let Some(dest) = data["Dest"].as_str() else { return invalid_request() };
+        let handle = format!("0x{:x}", device.next_call);
+        device.next_call += 1;
+        let line = data["Line"].as_str().unwrap_or("1").to_string();
+        device.calls.push(Call { handle, line, remote: dest.to_string(), held: false, started: Instant::now() });
+        return acknowledge().into_response()
+    }
+
+    // every other action works on an existing call
+    let Some(idx) = data["Ref"].as_str().and_then(|handle| device.call_index(handle)) else { return invalid_request() };
+    match action.as_str() {
+        "holdCall" => device.calls[idx].held = true,
+        "resumeCall" => device.calls[idx].held = false,
+        "endCall" | "rejectCall" | "ignoreCall" => {
+            device.calls.remove(idx);
+        },
+        "answerCall" | "sendDTMF" => {},
+        "transferCall" => {
+            let Some(dest) = data["TransferDest"].as_str() else { return invalid_request() };
+            // transferring to a party there's already a call with completes a consultative transfer, and the device drops out of both
+            device.calls.remove(idx);
+            device.calls.retain(|call| call.remote != dest);
+        },
+        _ => return (StatusCode::NOT_FOUND, Json(json!({"Status": "4004"}))).into_response()
+    }
+    acknowledge().into_response()
+}
+
+async fn call_status(State(sim): State<Simulator>) -> Json<Value> {
+    let device = sim.device();
+    // like the phone, leave out the data field when there are no calls
+    if device.calls.is_empty() {
+        return acknowledge()
+    }
+    let calls: Vec<Value> = device.calls.iter().map(|call| json!({"CallHandle": call.handle, "Type": "Outgoing", "Protocol": "Sip",
+        "CallState": if call.held { "Hold" } else { "Connected" }, "LineId": call.line, "RemotePartyName": call.remote,
+        "RemotePartyNumber": call.remote, "DurationInSeconds": call.started.elapsed().as_secs().to_string()})).collect();
+    success(json!(calls))
+}
+
+/// A digest challenge with a fresh nonce. `stale` tells the client its credentials were fine, but the nonce had expired.
+fn challenge(device: &mut Device, stale: bool) -> Response {
+    device.nonces.retain(|_, issued| issued.elapsed() < NONCE_LIFETIME);
+    let seed = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_nanos();
+    let nonce = format!("{:x}{:04x}", seed, device.nonces.len());
+    device.nonces.insert(nonce.clone(), Instant::now());
+    let mut www_auth = format!("Digest realm=\"{}\", qop=\"auth\", nonce=\"{}\"", DIGEST_REALM, nonce);
+    if stale {
+        www_auth.push_str(", stale=true");
+    }
+    (StatusCode::UNAUTHORIZED, [(header::WWW_AUTHENTICATE, www_auth)]).into_response()
+}
+
+enum Digest {
+    Valid,
+    /// the digest is correct, but for a nonce that expired or was never issued
+    Stale,
+    Invalid,
+}
+
+/// Check a digest Authorization header against the push credentials and the nonces this device issued
+fn verify_digest(authorization: &str, body: &[u8], device: &Device) -> Digest {
+    let Ok(auth) = AuthorizationHeader::parse(authorization) else { return Digest::Invalid };
+    if auth.username != device.value("apps.push.username") {
+        return Digest::Invalid
+    }
+    let context = AuthContext::new_with_method(device.value("apps.push.username"), device.value("apps.push.password"),
+        auth.uri.as_str(), Some(body), HttpMethod::from("POST"));
+    let mut expected = auth.clone();
+    expected.digest(&context);
+    if expected.response != auth.response {
+        return Digest::Invalid
+    }
+    match device.nonces.get(&auth.nonce) {
+        Some(issued) if issued.elapsed() < NONCE_LIFETIME => Digest::Valid,
+        _ => Digest::Stale
+    }
+}
+
+async fn push(State(sim): State<Simulator>, headers: HeaderMap, body: String) -> Response {
+    let mut device = sim.device();
+    if device.value("apps.push.messageType") == "0" || device.value("apps.push.username").is_empty() {
+        return StatusCode::FORBIDDEN.into_response()
+    }
+
+    let digest = headers.get(header::AUTHORIZATION)
+        .and_then(|auth| auth.to_str().ok())
+        .map_or(Digest::Invalid, |auth| verify_digest(auth, body.as_bytes(), &device));
+    match digest {
+        Digest::Valid => {},
+        Digest::Stale => return challenge(&mut device, true),
+        Digest::Invalid => return challenge(&mut device, false)
+    }
+
+    let content_type = headers.get(header::CONTENT_TYPE).and_then(|ct| ct.to_str().ok()).unwrap_or_default().to_string();
+    // like the phone, answer anything that passes auth with a 200, and put the outcome in the body.
+    // Rejections say plainly that they came from the simulator, with the keyword libpoly classifies them by.
+    if !body.contains("<PolycomIPPhone>") {
+        tracing::warn!(content_type = content_type, body = body, "rejecting push without a PolycomIPPhone body");
+        return (StatusCode::OK, "simulator: push body is not PolycomIPPhone XML").into_response()
+    }
+    if device.busy_until.is_some_and(|until| until > Instant::now()) {
+        tracing::warn!(content_type = content_type, body = body, "rejecting push while restarting");
+        return (StatusCode::OK, "simulator: device is busy restarting").into_response()
+    }
+    tracing::info!(content_type = content_type, body = body, "received push");
+    device.pushes.push(ReceivedPush { content_type, body });
+    (StatusCode::OK, "Push Message will be displayed successfully").into_response()
+}
+
+/// the `host:port` to listen on so that clients given `url` reach the simulator
+fn bind_address(url: &str) -> anyhow::Result<String> {
+    let uri: Uri = url.parse().with_context(|| format!("invalid URL {}", url))?;
+    if uri.scheme_str() != Some("http") {
+        bail!("the simulator only serves plain HTTP, so --url must start with http://");
+    }
+    let host = uri.host().context("--url has no host")?;
+    Ok(format!("{}:{}", host, uri.port_u16().unwrap_or(80)))
+}
+
+/// Serve a simulated phone until interrupted
+pub async fn run_simulate(url: &str, username: String, password: String, push_user: String, push_pass: String) -> anyhow::Result<()> {
+    init_tracing();
+    let sim = Simulator::new(&username, &password, &push_user, &push_pass);
+
+    let endpoint = bind_address(url)?;
+    let listener = tokio::net::TcpListener::bind(&endpoint).await.with_context(|| format!("unable to listen on {}", endpoint))?;
+    tracing::info!("simulating a {} ({}) on http://{}", MODEL_NUMBER, MAC_ADDRESS, listener.local_addr()?);
+    axum::serve(listener, sim.router()).await?;
+    Ok(())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use libpoly::{errors::PolyRestError, polyrest::{callctrl::{CallState, DialType}, PolyRest}, push::{MessageLevel, PushMessenger, PushRejection, PushResult, PushType}};
+    use super::*;
+
+    async fn start(sim: &Simulator) -> String {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let router = sim.router();
+        tokio::spawn(async move { axum::serve(listener, router).await.unwrap() });
+        url
+    }
+
+    #[tokio::test]
+    async fn rest_api() {
+        let sim = Simulator::new("Polycom", "789", "Push", "Push");
+        let url = start(&sim).await;
+        let handler = PolyRest::new("Polycom", "789", url.as_str(), false).unwrap();
+
+        assert_eq!(handler.device_info().await.unwrap().mac_address, MAC_ADDRESS);
+        handler.network_info().await.unwrap();
+        assert_eq!(handler.line_info().await.unwrap()[0].label, "1001");
+        assert_eq!(handler.all_call_logs().await.unwrap().len(), 3);
+
+        let results = handler.config_set([("reg.1.label", "Desk")]).await.unwrap();
+        assert!(results["reg.1.label"].is_success());
+        let values = handler.config_get(["reg.1.label", "reg.1.server.1.*"]).await.unwrap();
+        assert_eq!(values["reg.1.label"].value, "Desk");
+        assert_eq!(values["reg.1.label"].source, "web");
+        assert_eq!(values["reg.1.server.1.address"].value, "pbx.example.com");
+
+        handler.safe_reboot().await.unwrap();
+        assert_eq!(handler.poll_for_status().await.unwrap().state, libpoly::polyrest::mgmt::DeviceState::Restarting);
+
+        let wrong = PolyRest::new("Polycom", "wrong", url.as_str(), false).unwrap();
+        assert!(matches!(wrong.device_info().await, Err(PolyRestError::AuthError(_))));
+    }
+
+    #[tokio::test]
+    async fn call_control() {
+        let sim = Simulator::new("Polycom", "789", "Push", "Push");
+        let url = start(&sim).await;
+        let handler = PolyRest::new("Polycom", "789", url.as_str(), false).unwrap();
+
+        assert!(handler.call_status().await.unwrap().calls.is_empty());
+        handler.dial("1002".to_string(), 1, DialType::Sip).await.unwrap();
+        let calls = handler.call_status().await.unwrap().calls;
+        assert_eq!(calls.len(), 1);
+        assert_eq!(calls[0].remote_party_number, "1002");
+        assert_eq!(calls[0].state, CallState::Connected);
+
+        let original = calls[0].call_handle.clone();
+        let transfer = handler.start_consultative_transfer(original.clone(), "1003".to_string(), 1, DialType::Sip).await.unwrap();
+        let calls = handler.call_status().await.unwrap().calls;
+        assert_eq!(calls.len(), 2);
+        assert_eq!(calls.iter().find(|c| c.call_handle == original).unwrap().state, CallState::Hold);
+        assert_eq!(calls.iter().find(|c| c.call_handle == transfer.consult).unwrap().remote_party_number, "1003");
+
+        handler.complete_transfer(transfer).await.unwrap();
+        assert!(handler.call_status().await.unwrap().calls.is_empty());
+        assert!(handler.end_call(original).await.is_err());
+    }
+
+    #[test]
+    fn bind_addresses() {
+        assert_eq!(bind_address("http://127.0.0.1:8080").unwrap(), "127.0.0.1:8080");
+        assert_eq!(bind_address("http://localhost").unwrap(), "localhost:80");
+        assert!(bind_address("https://127.0.0.1:8443").is_err());
+    }
+
+    #[tokio::test]
+    async fn push_api() {
+        let sim = Simulator::new("Polycom", "789", "Push", "Push");
+        let url = start(&sim).await;
+
+        let handle = PushMessenger::new("Push", "Push", url.as_str(), false).unwrap();
+        assert_eq!(handle.send(MessageLevel::Normal, "Key:Home", PushType::Command).await.unwrap(), PushResult::Accepted);
+        let pushes = sim.pushes();
+        assert_eq!(pushes.len(), 1);
+        assert_eq!(pushes[0].content_type, "application/x-com-polycom-spipx");
+        assert!(pushes[0].body.contains("Key:Home"));
+
+        // the cached nonce is reused, then renewed once the device forgets it
+        handle.send(MessageLevel::Normal, "Key:Home", PushType::Command).await.unwrap();
+        assert_eq!(sim.device().nonces.len(), 1);
+        sim.device().nonces.clear();
+        handle.send(MessageLevel::Normal, "Key:Home", PushType::Command).await.unwrap();
+        assert_eq!(sim.pushes().len(), 3);
+
+        let wrong = PushMessenger::new("Push", "wrong", url.as_str(), false).unwrap();
+        assert_eq!(wrong.send(MessageLevel::Normal, "Key:Home", PushType::Command).await.unwrap(), PushResult::AuthFailed);
+        assert_eq!(sim.pushes().len(), 3);
+
+        sim.device().restart(false);
+        assert_eq!(handle.send(MessageLevel::Normal, "Key:Home", PushType::Command).await.unwrap(), PushResult::Rejected(PushRejection::Busy));
+        sim.device().busy_until = None;
+
+        sim.device().config.insert("apps.push.messageType".to_string(), ConfigResponseValue { value: "0".to_string(), source: "web".to_string() });
+        assert!(matches!(handle.send(MessageLevel::Normal, "Key:Home", PushType::Command).await, Err(PolyRestError::PushDisabled(_))));
+    }
+}
@@ -0,0 +1,300 @@
+use std::path::Path;
+use anyhow::Context;
+use indexmap::IndexMap;
+use libpoly::{catalog, polyrest::PolyRest};
+use serde::Deserialize;
+
+/// A parameter value in a desired-state file. Numbers and booleans are accepted for convenience, and sent as strings.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+enum ParamValue {
+    Str(String),
+    Int(i64),
+    Float(f64),
+    Bool(bool)
+}
+
+impl ParamValue {
+    fn into_string(self) -> String {
+        match self {
+            ParamValue::Str(s) => s,
+            ParamValue::Int(i) => i.to_string(),
+            ParamValue::Float(f) => f.to_string(),
+            ParamValue::Bool(b) => if b { "1".to_string() } else { "0".to_string() }
+        }
+    }
+}
+
+/// A named set of devices that share parameters
+#[derive(Debug, Deserialize)]
+pub struct Group {
+    /// Device URLs in the group
+    pub devices: Vec<String>,
+    #[serde(default)]
+    params: IndexMap<String, ParamValue>,
+}
+
+/// A desired-state file, read from TOML or YAML:
+/// ```toml
+/// [params]
+/// "up.backlight.idleIntensity" = 1
+///
+/// [groups.lobby]
+/// devices = ["https://192.168.1.9"]
+/// params = { "reg.1.label" = "Lobby" }
+///
+/// [devices."https://192.168.1.9"]
+/// "reg.1.address" = "1001"
+/// ```
+/// Device parameters override group parameters, which override the top-level parameters.
+#[derive(Debug, Deserialize)]
+pub struct DesiredState {
+    /// Parameters for every device
+    #[serde(default)]
+    params: IndexMap<String, ParamValue>,
+    #[serde(default)]
+    groups: IndexMap<String, Group>,
+    /// Parameters for individual devices, keyed by URL
+    #[serde(default)]
+    devices: IndexMap<String, IndexMap<String, ParamValue>>,
+}
+
+impl DesiredState {
+    pub fn load<P: AsRef<Path>>(path: P) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let raw = std::fs::read_to_string(path).with_context(|| format!("reading {}", path.to_string_lossy()))?;
+        let state = match path.extension().and_then(|ext| ext.to_str()) {
+            Some("yaml" | "yml") => serde_yaml::from_str(&raw)?,
+            Some("toml") => toml::from_str(&raw)?,
+            _ => anyhow::bail!("desired state must be a .toml, .yaml or .yml file")
+        };
+        Ok(state)
+    }
+
+    /// The devices named in the file, in the order they first appear.
+    /// If the file names no devices, `default_url` is the only target.
+    pub fn targets(&self, default_url: &str) -> Vec<String> {
+        let mut targets: Vec<String> = Vec::new();
+        let named = self.groups.values().flat_map(|group| group.devices.iter()).chain(self.devices.keys());
+        for url in named {
+            if !targets.contains(url) {
+                targets.push(url.clone());
+            }
+        }
+        if targets.is_empty() {
+            targets.push(default_url.to_string());
+        }
+        targets
+    }
+
+    /// The parameters that should be set on the given device
+    pub fn params_for(&self, url: &str) -> IndexMap<String, String> {
+        let groups = self.groups.values().filter(|group| group.devices.iter().any(|dev| dev == url)).map(|group| &group.params);
+        std::iter::once(&self.params)
+            .chain(groups)
+            .chain(self.devices.get(url))
+            .flat_map(|params| params.iter())
+            .map(|(key, val)| (key.clone(), val.clone().into_string()))
+            .collect()
+    }
+}
+
+/// A single parameter that needs to change on a device
+#[derive(Debug)]
+pub struct PlannedChange {
+    pub key: String,
+    /// The current value, or `None` if the device doesn't report the parameter
+    pub current: Option<String>,
+    pub desired: String,
+}
+
+/// The changes needed to bring one device to its desired state
+#[derive(Debug)]
+pub struct DevicePlan {
+    pub url: String,
+    pub changes: Vec<PlannedChange>,
+    pub unchanged: usize,
+}
+
+impl DevicePlan {
+    fn print(&self) {
+        println!("{}:", self.url);
+        for change in &self.changes {
+            match &change.current {
+                Some(current) => println!("  ~ {}: {:?} -> {:?}", change.key, current, change.desired),
+                None => println!("  + {}: {:?}", change.key, change.desired),
+            }
+        }
+        println!("  {} to change, {} unchanged", self.changes.len(), self.unchanged);
+    }
+}
+
+/// Compare the desired parameters against the current values on the device
+async fn plan_device(handler: &PolyRest, url: &str, desired: IndexMap<String, String>) -> anyhow::Result<DevicePlan> {
+    let current = handler.config_get(desired.keys().cloned()).await?;
+    let mut changes = Vec::new();
+    let mut unchanged = 0;
+    for (key, desired) in desired {
+        let current = current.get(&key).map(|val| val.value.clone());
+        if current.as_ref() == Some(&desired) {
+            unchanged += 1;
+        } else {
+            changes.push(PlannedChange { key, current, desired });
+        }
+    }
+    Ok(DevicePlan { url: url.to_string(), changes, unchanged })
+}
+
+/// Plan every device, and check every planned change against the parameter catalog,
+/// so a bad value fails the whole plan before any device is touched
+async fn plan_all(state: &DesiredState, allow_unknown: bool, user: &str, pass: &str, url: &str) -> anyhow::Result<Vec<(PolyRest, DevicePlan)>> {
+    let mut plans = Vec::new();
+    let mut invalid = 0;
+    for target in state.targets(url) {
+        let handler = PolyRest::new(user, pass, target.as_str(), true)?.with_allow_unknown_params(allow_unknown);
+        let plan = plan_device(&handler, &target, state.params_for(&target)).await.with_context(|| format!("planning {}", target))?;
+        plan.print();
+        for change in &plan.changes {
+            if let Err(err) = catalog::validate(&change.key, &change.desired, allow_unknown) {
+                eprintln!("{}: {}", plan.url, err);
+                invalid += 1;
+            }
+        }
+        plans.push((handler, plan));
+    }
+    if invalid > 0 {
+        anyhow::bail!("{} planned changes are invalid; nothing was applied", invalid)
+    }
+    Ok(plans)
+}
+
+pub async fn run_plan(path: &str, allow_unknown: bool, user: &str, pass: &str, url: &str) -> anyhow::Result<()> {
+    let state = DesiredState::load(path)?;
+    plan_all(&state, allow_unknown, user, pass, url).await?;
+    Ok(())
+}
+
+/// Apply the plan to each device, then read the values back to verify them
+pub async fn run_apply(path: &str, yes: bool, allow_unknown: bool, user: &str, pass: &str, url: &str) -> anyhow::Result<()> {
+    let state = DesiredState::load(path)?;
+    let plans = plan_all(&state, allow_unknown, user, pass, url).await?;
+
+    let total: usize = plans.iter().map(|(_, plan)| plan.changes.len()).sum();
+    if total == 0 {
+        println!("nothing to do");
+        return Ok(())
+    }
+    if !yes && !crate::confirm(&format!("Apply {} changes to {} devices?", total, plans.len()))? {
+        return Ok(())
+    }
+
+    let mut failed = 0;
+    for (handler, plan) in plans {
+        if plan.changes.is_empty() {
+            continue;
+        }
+        let changes: IndexMap<String, String> = plan.changes.into_iter().map(|change| (change.key, change.desired)).collect();
+        // keep rolling out to the other devices if one can't be reached
+        let results = match handler.config_set(changes.clone()).await {
+            Ok(results) => results,
+            Err(err) => {
+                eprintln!("{}: {}", plan.url, err);
+                failed += changes.len();
+                continue
+            }
+        };
+        for (key, status) in results {
+            if !status.is_success() {
+                eprintln!("{}: {}: {}", plan.url, key, status);
+                failed += 1;
+            }
+        }
+
+        let readback = match handler.config_get(changes.keys().cloned()).await {
+            Ok(readback) => readback,
+            Err(err) => {
+                eprintln!("{}: could not verify changes: {}", plan.url, err);
+                failed += changes.len();
+                continue
+            }
+        };
+        for (key, desired) in &changes {
+            let actual = readback.get(key).map(|val| val.value.as_str());
+            if actual != Some(desired.as_str()) {
+                eprintln!("{}: {}: expected {:?} after apply, device reports {:?}", plan.url, key, desired, actual);
+                failed += 1;
+            }
+        }
+        println!("{}: applied {} changes", plan.url, changes.len());
+    }
+
+    if failed > 0 {
+        anyhow::bail!("{} changes failed to apply or verify", failed)
+    }
+    Ok(())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STATE: &str = r#"
+[params]
+"up.backlight.idleIntensity" = 1
+"reg.1.label" = "Phone"
+
+[groups.lobby]
+devices = ["https://192.168.1.9", "https://192.168.1.10"]
+params = { "reg.1.label" = "Lobby" }
+
+[devices."https://192.168.1.9"]
+"reg.1.address" = "1001"
+"#;
+
+    #[test]
+    fn resolve_params() {
+        let state: DesiredState = toml::from_str(STATE).unwrap();
+        assert_eq!(state.targets("https://fallback"), vec!["https://192.168.1.9", "https://192.168.1.10"]);
+
+        let params = state.params_for("https://192.168.1.9");
+        assert_eq!(params["up.backlight.idleIntensity"], "1");
+        assert_eq!(params["reg.1.label"], "Lobby");
+        assert_eq!(params["reg.1.address"], "1001");
+
+        let params = state.params_for("https://192.168.1.10");
+        assert!(!params.contains_key("reg.1.address"));
+    }
+
+    #[test]
+    fn yaml_state() {
+        let state: DesiredState = serde_yaml::from_str("params:\n  reg.1.label: Desk\n  feature.enhancedFeatureKeys.enabled: true\n").unwrap();
+        assert_eq!(state.targets("https://fallback"), vec!["https://fallback"]);
+        assert_eq!(state.params_for("https://fallback")["feature.enhancedFeatureKeys.enabled"], "1");
+    }
+
+    async fn start_simulator() -> String {
+        let sim = crate::simulate::Simulator::new("Polycom", "789", "Push", "Push");
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let router = sim.router();
+        tokio::spawn(async move { axum::serve(listener, router).await.unwrap() });
+        url
+    }
+
+    #[tokio::test]
+    async fn invalid_change_stops_apply() {
+        let (first, second) = (start_simulator().await, start_simulator().await);
+        // the second device's value is out of range, so neither device should be changed
+        let state = format!("[devices.\"{}\"]\n\"reg.1.label\" = \"Lobby\"\n[devices.\"{}\"]\n\"up.backlight.idleIntensity\" = 9\n", first, second);
+        let path = std::env::temp_dir().join(format!("polycli-state-test-{}.toml", std::process::id()));
+        std::fs::write(&path, state).unwrap();
+
+        let result = run_apply(&path.to_string_lossy(), true, false, "Polycom", "789", &first).await;
+        std::fs::remove_file(&path).unwrap();
+        assert!(result.unwrap_err().to_string().contains("nothing was applied"));
+
+        let handler = PolyRest::new("Polycom", "789", first.as_str(), false).unwrap();
+        assert_eq!(handler.config_get(["reg.1.label"]).await.unwrap()["reg.1.label"].value, "1001");
+    }
+}